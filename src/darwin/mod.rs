@@ -338,14 +338,24 @@ impl Font {
         let strikeout_position = (line_height / 2. - descent) as f32;
         let strikeout_thickness = underline_thickness;
 
+        let (overline_position, overline_thickness) =
+            Metrics::overline(ascent as f32, underline_thickness);
+
+        let double_underline_position = underline_position - 2. * underline_thickness;
+
         Metrics {
             average_advance,
             line_height,
+            ascent: ascent as f32,
             descent: -(descent as f32),
+            line_gap: leading as f32,
             underline_position,
             underline_thickness,
             strikeout_position,
             strikeout_thickness,
+            overline_position,
+            overline_thickness,
+            double_underline_position,
         }
     }
 
@@ -396,6 +406,7 @@ impl Font {
                 top: 0,
                 left: 0,
                 advance: (0, 0),
+                side_bearings: (0., 0.),
                 buffer: BitmapBuffer::Rgb(Vec::new()),
             };
         }
@@ -458,6 +469,7 @@ impl Font {
             width: rasterized_width as i32,
             height: rasterized_height as i32,
             advance: (0, 0),
+            side_bearings: (0., 0.),
             buffer,
         }
     }
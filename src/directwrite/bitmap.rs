@@ -0,0 +1,408 @@
+//! Embedded bitmap strikes of bitmap fonts.
+//!
+//! DirectWrite only rasterizes glyph outlines, so fonts which ship their glyphs as bitmaps in
+//! `EBDT` or `CBDT` tables have to be read manually.
+
+use std::ptr;
+
+use dwrote::FontFace;
+use winapi::shared::minwindef::LPVOID;
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror::{RPC_E_CHANGED_MODE, SUCCEEDED};
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx, CoUninitialize};
+use winapi::um::objbase::COINIT_MULTITHREADED;
+use winapi::um::objidlbase::IStream;
+use winapi::um::wincodec::{
+    CLSID_WICImagingFactory, GUID_WICPixelFormat32bppPBGRA, IWICBitmapDecoder,
+    IWICBitmapFrameDecode, IWICBitmapSource, IWICFormatConverter, IWICImagingFactory, IWICStream,
+    WICBitmapDitherTypeNone, WICBitmapPaletteTypeCustom, WICDecodeMetadataCacheOnDemand,
+};
+use winapi::Interface;
+use wio::com::ComPtr;
+
+use super::tables::{self, read_u16, read_u32};
+use super::{Font, FontTable};
+use crate::{BitmapBuffer, Error, RasterizedGlyph};
+
+/// Size of the bitmap size records in the location table.
+const BITMAP_SIZE_RECORD_SIZE: usize = 48;
+
+/// Size of the small glyph metrics.
+const SMALL_METRICS_SIZE: usize = 5;
+
+/// Size of the big glyph metrics.
+const BIG_METRICS_SIZE: usize = 8;
+
+/// Location and data tables of a font's embedded bitmaps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BitmapTables {
+    location: u32,
+    data: u32,
+}
+
+impl BitmapTables {
+    /// Find the embedded bitmap tables of a font face, preferring color bitmaps.
+    ///
+    /// Returns `None` for fonts without embedded bitmaps.
+    pub fn find(face: &FontFace) -> Result<Option<Self>, Error> {
+        for (location, data) in [(tables::CBLC, tables::CBDT), (tables::EBLC, tables::EBDT)] {
+            if FontTable::exists(face, location)? && FontTable::exists(face, data)? {
+                return Ok(Some(BitmapTables { location, data }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Check if the bitmaps are in color.
+    fn is_color(&self) -> bool {
+        self.location == tables::CBLC
+    }
+}
+
+/// Bitmap strike of a font.
+struct Strike {
+    index_array_offset: usize,
+    index_count: usize,
+    ppem: u8,
+    bit_depth: u8,
+}
+
+/// Metrics of an embedded glyph bitmap.
+#[derive(Copy, Clone)]
+struct GlyphMetrics {
+    height: u8,
+    width: u8,
+    bearing_x: i8,
+    bearing_y: i8,
+}
+
+/// Raw glyph image inside the bitmap data table.
+struct GlyphImage<'a> {
+    format: u16,
+    data: &'a [u8],
+
+    /// Metrics shared by all glyphs in the index subtable.
+    metrics: Option<GlyphMetrics>,
+}
+
+/// Rasterize a glyph from the font's embedded bitmaps.
+///
+/// The strike closest to `em_size` is used without scaling its bitmap. Fonts which also have
+/// outlines only use strikes exactly matching `em_size`, since their outlines look better at
+/// every other size.
+///
+/// Bitmaps are used as they are, so neither the rasterizer's transform nor the antialiasing of its
+/// rendering mode are applied to them.
+///
+/// Returns `None` if there is no bitmap for the glyph.
+pub fn rasterize_embedded_bitmap(
+    font: &Font,
+    glyph_index: u16,
+    em_size: f32,
+    character: char,
+    advance: (i32, i32),
+) -> Result<Option<RasterizedGlyph>, Error> {
+    let (location, data) = match borrow_tables(font)? {
+        Some(tables) => tables,
+        None => return Ok(None),
+    };
+
+    let strike = match find_strike(&location, glyph_index, em_size, font.has_outlines) {
+        Some(strike) => strike,
+        None => return Ok(None),
+    };
+
+    let image = match find_glyph(&location, &data, &strike, glyph_index) {
+        Some(image) => image,
+        None => return Ok(None),
+    };
+
+    let (metrics, image_data) = match image.format {
+        1 | 2 | 17 => (read_metrics(image.data), image.data.get(SMALL_METRICS_SIZE..)),
+        6 | 7 | 18 => (read_metrics(image.data), image.data.get(BIG_METRICS_SIZE..)),
+        5 | 19 => (image.metrics, Some(image.data)),
+        _ => return Ok(None),
+    };
+    let (metrics, image_data) = match metrics.zip(image_data) {
+        Some(glyph) => glyph,
+        None => return Ok(None),
+    };
+
+    let (width, height, buffer) = match image.format {
+        // PNG images, prefixed by their length.
+        17..=19 => {
+            let length = read_u32(image_data, 0).map_or(0, |length| length as usize);
+            let png = match image_data.get(4..4 + length) {
+                Some(png) => png,
+                None => return Ok(None),
+            };
+
+            let (width, height, rgba) = decode_png(png)?;
+            (width, height, BitmapBuffer::Rgba(rgba))
+        },
+        format => {
+            let byte_aligned = format == 1 || format == 6;
+            let coverage = read_coverage(image_data, metrics, strike.bit_depth, byte_aligned);
+            let coverage = match coverage {
+                Some(coverage) => coverage,
+                None => return Ok(None),
+            };
+
+            let rgb = coverage.iter().flat_map(|&alpha| [alpha; 3]).collect();
+            (i32::from(metrics.width), i32::from(metrics.height), BitmapBuffer::Rgb(rgb))
+        },
+    };
+
+    Ok(Some(RasterizedGlyph {
+        character,
+        width,
+        height,
+        top: i32::from(metrics.bearing_y),
+        left: i32::from(metrics.bearing_x),
+        advance,
+        buffer,
+        ..Default::default()
+    }))
+}
+
+/// Check if a glyph has a color bitmap in the font's `CBDT` table.
+pub fn has_color_bitmap(font: &Font, glyph_index: u16) -> Result<bool, Error> {
+    if !font.bitmap_tables.is_some_and(|tables| tables.is_color()) {
+        return Ok(false);
+    }
+
+    let (location, data) = match borrow_tables(font)? {
+        Some(tables) => tables,
+        None => return Ok(false),
+    };
+
+    Ok(find_strike(&location, glyph_index, 0., false)
+        .and_then(|strike| find_glyph(&location, &data, &strike, glyph_index))
+        .is_some())
+}
+
+/// Borrow the location and data tables of a font's embedded bitmaps.
+fn borrow_tables(font: &Font) -> Result<Option<(FontTable<'_>, FontTable<'_>)>, Error> {
+    let bitmap_tables = match font.bitmap_tables {
+        Some(bitmap_tables) => bitmap_tables,
+        None => return Ok(None),
+    };
+
+    let location = FontTable::new(&font.face, bitmap_tables.location)?;
+    let data = FontTable::new(&font.face, bitmap_tables.data)?;
+    Ok(location.zip(data))
+}
+
+/// Find the strike closest to `em_size` which contains a glyph.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/eblc.
+fn find_strike(location: &[u8], glyph_index: u16, em_size: f32, exact: bool) -> Option<Strike> {
+    let size_count = read_u32(location, 4)? as usize;
+
+    (0..size_count)
+        .filter_map(|size| {
+            let offset = 8 + size * BITMAP_SIZE_RECORD_SIZE;
+            let start_glyph = read_u16(location, offset + 40)?;
+            let end_glyph = read_u16(location, offset + 42)?;
+            if !(start_glyph..=end_glyph).contains(&glyph_index) {
+                return None;
+            }
+
+            Some(Strike {
+                index_array_offset: read_u32(location, offset)? as usize,
+                index_count: read_u32(location, offset + 8)? as usize,
+                ppem: *location.get(offset + 45)?,
+                bit_depth: *location.get(offset + 46)?,
+            })
+        })
+        .filter(|strike| !exact || f32::from(strike.ppem) == em_size.round())
+        .min_by(|a, b| {
+            let distance = |strike: &Strike| (f32::from(strike.ppem) - em_size).abs();
+            distance(a).total_cmp(&distance(b))
+        })
+}
+
+/// Find the image of a glyph in a strike.
+fn find_glyph<'a>(
+    location: &[u8],
+    data: &'a [u8],
+    strike: &Strike,
+    glyph_index: u16,
+) -> Option<GlyphImage<'a>> {
+    let subtable = (0..strike.index_count).find_map(|index| {
+        let offset = strike.index_array_offset + index * 8;
+        let first_glyph = read_u16(location, offset)?;
+        let last_glyph = read_u16(location, offset + 2)?;
+        let subtable = strike.index_array_offset + read_u32(location, offset + 4)? as usize;
+        (first_glyph..=last_glyph).contains(&glyph_index).then_some((first_glyph, subtable))
+    });
+    let (first_glyph, subtable) = subtable?;
+
+    let index_format = read_u16(location, subtable)?;
+    let format = read_u16(location, subtable + 2)?;
+    let image_offset = read_u32(location, subtable + 4)? as usize;
+    let glyph = usize::from(glyph_index - first_glyph);
+
+    let (start, end, metrics) = match index_format {
+        1 => {
+            let start = read_u32(location, subtable + 8 + glyph * 4)?;
+            let end = read_u32(location, subtable + 8 + (glyph + 1) * 4)?;
+            (start as usize, end as usize, None)
+        },
+        2 => {
+            let image_size = read_u32(location, subtable + 8)? as usize;
+            let metrics = read_metrics(location.get(subtable + 12..)?)?;
+            (glyph * image_size, (glyph + 1) * image_size, Some(metrics))
+        },
+        3 => {
+            let start = read_u16(location, subtable + 8 + glyph * 2)?;
+            let end = read_u16(location, subtable + 8 + (glyph + 1) * 2)?;
+            (usize::from(start), usize::from(end), None)
+        },
+        4 => {
+            let glyph_count = read_u32(location, subtable + 8)? as usize;
+            let position = (0..glyph_count)
+                .find(|i| read_u16(location, subtable + 12 + i * 4) == Some(glyph_index))?;
+            let start = read_u16(location, subtable + 12 + position * 4 + 2)?;
+            let end = read_u16(location, subtable + 12 + (position + 1) * 4 + 2)?;
+            (usize::from(start), usize::from(end), None)
+        },
+        5 => {
+            let image_size = read_u32(location, subtable + 8)? as usize;
+            let metrics = read_metrics(location.get(subtable + 12..)?)?;
+            let glyph_count = read_u32(location, subtable + 20)? as usize;
+            let position = (0..glyph_count)
+                .find(|i| read_u16(location, subtable + 24 + i * 2) == Some(glyph_index))?;
+            (position * image_size, (position + 1) * image_size, Some(metrics))
+        },
+        _ => return None,
+    };
+
+    let data = data.get(image_offset + start..image_offset + end)?;
+    Some(GlyphImage { format, data, metrics })
+}
+
+/// Read small or big glyph metrics, which share the same layout for horizontal metrics.
+fn read_metrics(data: &[u8]) -> Option<GlyphMetrics> {
+    match *data.get(..4)? {
+        [height, width, bearing_x, bearing_y] => Some(GlyphMetrics {
+            height,
+            width,
+            bearing_x: bearing_x as i8,
+            bearing_y: bearing_y as i8,
+        }),
+        _ => None,
+    }
+}
+
+/// Read the coverage of a monochrome or grayscale bitmap.
+///
+/// Byte aligned bitmaps start every row at a new byte, while bit aligned bitmaps are packed
+/// without any padding.
+fn read_coverage(
+    data: &[u8],
+    metrics: GlyphMetrics,
+    bit_depth: u8,
+    byte_aligned: bool,
+) -> Option<Vec<u8>> {
+    let (width, height, depth) =
+        (usize::from(metrics.width), usize::from(metrics.height), usize::from(bit_depth));
+    if !matches!(depth, 1 | 2 | 4 | 8) {
+        return None;
+    }
+
+    let row_bits = if byte_aligned { (width * depth).div_ceil(8) * 8 } else { width * depth };
+    let max_value = (1 << depth) - 1;
+
+    let mut coverage = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for column in 0..width {
+            let bit = row * row_bits + column * depth;
+            let byte = *data.get(bit / 8)?;
+            let value = (usize::from(byte) >> (8 - depth - bit % 8)) & max_value;
+            coverage.push((value * 255 / max_value) as u8);
+        }
+    }
+
+    Some(coverage)
+}
+
+/// Decode a PNG image to RGBA pixels with premultiplied alpha.
+fn decode_png(png: &[u8]) -> Result<(i32, i32, Vec<u8>), Error> {
+    // The imaging component requires COM, which might not be initialized on this thread yet.
+    let hr = unsafe { CoInitializeEx(ptr::null_mut(), COINIT_MULTITHREADED) };
+    let result = unsafe { decode_png_with_com(png) };
+
+    if SUCCEEDED(hr) {
+        unsafe { CoUninitialize() };
+    } else if hr != RPC_E_CHANGED_MODE {
+        return Err(Error::from(hr));
+    }
+
+    result
+}
+
+unsafe fn decode_png_with_com(png: &[u8]) -> Result<(i32, i32, Vec<u8>), Error> {
+    let mut factory: *mut IWICImagingFactory = ptr::null_mut();
+    check(CoCreateInstance(
+        &CLSID_WICImagingFactory,
+        ptr::null_mut(),
+        CLSCTX_INPROC_SERVER,
+        &IWICImagingFactory::uuidof(),
+        &mut factory as *mut *mut IWICImagingFactory as *mut LPVOID,
+    ))?;
+    let factory = ComPtr::from_raw(factory);
+
+    let mut stream: *mut IWICStream = ptr::null_mut();
+    check(factory.CreateStream(&mut stream))?;
+    let stream = ComPtr::from_raw(stream);
+    check(stream.InitializeFromMemory(png.as_ptr() as *mut u8, png.len() as u32))?;
+
+    let mut decoder: *mut IWICBitmapDecoder = ptr::null_mut();
+    check(factory.CreateDecoderFromStream(
+        stream.as_raw() as *const IStream,
+        ptr::null(),
+        WICDecodeMetadataCacheOnDemand,
+        &mut decoder,
+    ))?;
+    let decoder = ComPtr::from_raw(decoder);
+
+    let mut frame: *mut IWICBitmapFrameDecode = ptr::null_mut();
+    check(decoder.GetFrame(0, &mut frame))?;
+    let frame = ComPtr::from_raw(frame);
+
+    let mut converter: *mut IWICFormatConverter = ptr::null_mut();
+    check(factory.CreateFormatConverter(&mut converter))?;
+    let converter = ComPtr::from_raw(converter);
+    check(converter.Initialize(
+        frame.as_raw() as *const IWICBitmapSource,
+        &GUID_WICPixelFormat32bppPBGRA,
+        WICBitmapDitherTypeNone,
+        ptr::null(),
+        0.,
+        WICBitmapPaletteTypeCustom,
+    ))?;
+
+    let (mut width, mut height) = (0, 0);
+    check(converter.GetSize(&mut width, &mut height))?;
+
+    let mut buffer = vec![0; width as usize * height as usize * 4];
+    check(converter.CopyPixels(ptr::null(), width * 4, buffer.len() as u32, buffer.as_mut_ptr()))?;
+
+    // Convert from BGRA to RGBA.
+    for pixel in buffer.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+
+    Ok((width as i32, height as i32, buffer))
+}
+
+fn check(hr: HRESULT) -> Result<(), Error> {
+    if SUCCEEDED(hr) {
+        Ok(())
+    } else {
+        Err(Error::from(hr))
+    }
+}
@@ -0,0 +1,118 @@
+//! Compositing of multiple glyphs into a single bitmap.
+
+use crate::{BitmapBuffer, RasterizedGlyph};
+
+/// Combine positioned glyphs into a single glyph.
+///
+/// Every glyph is placed at its pen position relative to the origin, with the Y axis pointing
+/// up. The result is a [`BitmapBuffer::Rgba`] with premultiplied alpha if any of the glyphs is in
+/// color, otherwise the coverage of all glyphs is merged into a [`BitmapBuffer::Rgb`] buffer.
+pub fn composite(
+    character: char,
+    glyphs: &[(RasterizedGlyph, (i32, i32))],
+    advance: (i32, i32),
+) -> RasterizedGlyph {
+    let is_color = glyphs.iter().any(|(glyph, _)| matches!(glyph.buffer, BitmapBuffer::Rgba(_)));
+    let visible: Vec<_> =
+        glyphs.iter().filter(|(glyph, _)| glyph.width > 0 && glyph.height > 0).collect();
+
+    if visible.is_empty() {
+        let buffer =
+            if is_color { BitmapBuffer::Rgba(Vec::new()) } else { BitmapBuffer::Rgb(Vec::new()) };
+        return RasterizedGlyph { character, advance, buffer, ..Default::default() };
+    }
+
+    let (mut left, mut top, mut right, mut bottom) = (i32::MAX, i32::MIN, i32::MIN, i32::MAX);
+    for (glyph, (x, y)) in &visible {
+        left = left.min(x + glyph.left);
+        top = top.max(y + glyph.top);
+        right = right.max(x + glyph.left + glyph.width);
+        bottom = bottom.min(y + glyph.top - glyph.height);
+    }
+
+    let width = right - left;
+    let height = top - bottom;
+    let channels = if is_color { 4 } else { 3 };
+    let mut buffer = vec![0u8; (width * height) as usize * channels];
+
+    for (glyph, (x, y)) in visible {
+        let x_offset = x + glyph.left - left;
+        let y_offset = top - (y + glyph.top);
+
+        for row in 0..glyph.height {
+            for column in 0..glyph.width {
+                let source = pixel(glyph, row, column);
+                let index = ((y_offset + row) * width + x_offset + column) as usize * channels;
+                let target = &mut buffer[index..index + channels];
+
+                if is_color {
+                    // Premultiplied source-over blending.
+                    let inverse_alpha = 255 - u16::from(source[3]);
+                    for (target, source) in target.iter_mut().zip(source) {
+                        let blended = u16::from(source) + u16::from(*target) * inverse_alpha / 255;
+                        *target = blended.min(255) as u8;
+                    }
+                } else {
+                    for (target, source) in target.iter_mut().zip(source) {
+                        *target = (*target).max(source);
+                    }
+                }
+            }
+        }
+    }
+
+    let buffer = if is_color { BitmapBuffer::Rgba(buffer) } else { BitmapBuffer::Rgb(buffer) };
+
+    let side_bearings = (left as f32, (advance.0 - right) as f32);
+
+    RasterizedGlyph { character, width, height, top, left, advance, side_bearings, buffer }
+}
+
+/// Read a pixel as premultiplied RGBA.
+///
+/// Coverage masks are treated as white with the coverage as alpha.
+fn pixel(glyph: &RasterizedGlyph, row: i32, column: i32) -> [u8; 4] {
+    let index = (row * glyph.width + column) as usize;
+    match &glyph.buffer {
+        BitmapBuffer::Rgb(buffer) => {
+            let [r, g, b] = [buffer[index * 3], buffer[index * 3 + 1], buffer[index * 3 + 2]];
+            [r, g, b, r.max(g).max(b)]
+        },
+        BitmapBuffer::Rgba(buffer) => {
+            let rgba = &buffer[index * 4..index * 4 + 4];
+            [rgba[0], rgba[1], rgba[2], rgba[3]]
+        },
+        BitmapBuffer::Alpha(buffer) => [buffer[index]; 4],
+        BitmapBuffer::Bitmap1 { data, stride_bits } => {
+            let bit = row as usize * stride_bits + column as usize;
+            let coverage = if data[bit / 8] & (0x80 >> (bit % 8)) != 0 { 255 } else { 0 };
+            [coverage; 4]
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_side_by_side() {
+        let glyph = RasterizedGlyph {
+            width: 1,
+            height: 1,
+            top: 1,
+            buffer: BitmapBuffer::Rgb(vec![255; 3]),
+            ..Default::default()
+        };
+
+        let glyphs = [(glyph.clone(), (0, 0)), (glyph, (2, 0))];
+        let composited = composite('a', &glyphs, (3, 0));
+
+        assert_eq!((composited.width, composited.height, composited.top), (3, 1, 1));
+        let expected = [255, 255, 255, 0, 0, 0, 255, 255, 255];
+        match composited.buffer {
+            BitmapBuffer::Rgb(buffer) => assert_eq!(buffer, expected),
+            _ => panic!("expected RGB glyph"),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Rasterization of layered color glyphs.
+
+use std::ptr;
+
+use dwrote::{DWRITE_TEXTURE_ALIASED_1x1, FontFace, GlyphOffset, DWRITE_GLYPH_RUN};
+use winapi::ctypes::c_void;
+use winapi::shared::windef::RECT;
+use winapi::shared::winerror::{DWRITE_E_NOCOLOR, S_OK};
+use winapi::um::dwrite_1::DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE;
+use winapi::um::dwrite_2::{
+    IDWriteColorGlyphRunEnumerator, IDWriteFontFace2, DWRITE_COLOR_GLYPH_RUN,
+};
+use winapi::um::unknwnbase::IUnknown;
+use winapi::Interface;
+use wio::com::ComPtr;
+
+use super::{glyph_run_analysis, RasterizationSettings};
+use crate::{BitmapBuffer, Error, RasterizedGlyph};
+
+/// Palette index of layers which should be drawn with the text color.
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+/// Single color layer of a glyph.
+struct Layer {
+    bounds: RECT,
+    coverage: Vec<u8>,
+    color: [f32; 4],
+}
+
+/// Rasterize a glyph with color layers.
+///
+/// The layers are composited into a [`BitmapBuffer::Rgba`] buffer with premultiplied alpha.
+/// Layers using the text color are drawn in white. Returns `None` if the glyph has no color
+/// layers.
+pub fn rasterize_color_glyph(
+    settings: &RasterizationSettings,
+    glyph_run: &DWRITE_GLYPH_RUN,
+    palette_index: u32,
+    character: char,
+    advance: (i32, i32),
+) -> Result<Option<RasterizedGlyph>, Error> {
+    let layers = match color_layers(settings, glyph_run, palette_index)? {
+        Some(layers) => layers,
+        None => return Ok(None),
+    };
+
+    // Color glyphs without any visible layer, like whitespace.
+    if layers.is_empty() {
+        return Ok(Some(RasterizedGlyph {
+            character,
+            advance,
+            buffer: BitmapBuffer::Rgba(Vec::new()),
+            ..Default::default()
+        }));
+    }
+
+    let mut bounds = RECT { left: i32::MAX, top: i32::MAX, right: i32::MIN, bottom: i32::MIN };
+    for layer in &layers {
+        bounds.left = bounds.left.min(layer.bounds.left);
+        bounds.top = bounds.top.min(layer.bounds.top);
+        bounds.right = bounds.right.max(layer.bounds.right);
+        bounds.bottom = bounds.bottom.max(layer.bounds.bottom);
+    }
+
+    let width = bounds.right - bounds.left;
+    let height = bounds.bottom - bounds.top;
+    let mut buffer = vec![0.; (width * height * 4) as usize];
+
+    // Composite all layers from bottom to top.
+    for layer in &layers {
+        let layer_width = layer.bounds.right - layer.bounds.left;
+        for (i, &coverage) in layer.coverage.iter().enumerate() {
+            let x = layer.bounds.left - bounds.left + i as i32 % layer_width;
+            let y = layer.bounds.top - bounds.top + i as i32 / layer_width;
+            let pixel = &mut buffer[((y * width + x) * 4) as usize..][..4];
+
+            let alpha = f32::from(coverage) / 255. * layer.color[3];
+            for (channel, value) in pixel.iter_mut().enumerate() {
+                let source = if channel == 3 { alpha } else { layer.color[channel] * alpha };
+                *value = source + *value * (1. - alpha);
+            }
+        }
+    }
+
+    let buffer = buffer.into_iter().map(|value| (value * 255.).round() as u8).collect();
+
+    Ok(Some(RasterizedGlyph {
+        character,
+        width,
+        height,
+        top: -bounds.top,
+        left: bounds.left,
+        advance,
+        buffer: BitmapBuffer::Rgba(buffer),
+        ..Default::default()
+    }))
+}
+
+/// Convert RGBA pixels from premultiplied to straight alpha.
+pub fn unpremultiply(buffer: &mut [u8]) {
+    for pixel in buffer.chunks_exact_mut(4) {
+        let alpha = u16::from(pixel[3]);
+        if alpha == 0 {
+            continue;
+        }
+
+        for channel in &mut pixel[..3] {
+            *channel = ((u16::from(*channel) * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+/// Split a glyph run into its visible color layers.
+///
+/// Returns `None` if the glyph run has no color layers.
+fn color_layers(
+    settings: &RasterizationSettings,
+    glyph_run: &DWRITE_GLYPH_RUN,
+    palette_index: u32,
+) -> Result<Option<Vec<Layer>>, Error> {
+    let enumerator = match translate_color_glyph_run(settings, glyph_run, palette_index)? {
+        Some(enumerator) => enumerator,
+        None => return Ok(None),
+    };
+
+    let mut layers = Vec::new();
+    loop {
+        let mut has_run = 0;
+        let hr = unsafe { enumerator.MoveNext(&mut has_run) };
+        if hr != S_OK {
+            return Err(Error::from(hr));
+        } else if has_run == 0 {
+            break;
+        }
+
+        let mut color_run: *const DWRITE_COLOR_GLYPH_RUN = ptr::null();
+        let hr = unsafe { enumerator.GetCurrentRun(&mut color_run) };
+        if hr != S_OK || color_run.is_null() {
+            return Err(Error::from(hr));
+        }
+        let color_run = unsafe { &*color_run };
+
+        let glyph_analysis = glyph_run_analysis(
+            settings,
+            &color_run.glyphRun,
+            DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+            (color_run.baselineOriginX, color_run.baselineOriginY),
+        )?;
+
+        let bounds = glyph_analysis.get_alpha_texture_bounds(DWRITE_TEXTURE_ALIASED_1x1)?;
+        if bounds.right <= bounds.left || bounds.bottom <= bounds.top {
+            continue;
+        }
+
+        let coverage = glyph_analysis.create_alpha_texture(DWRITE_TEXTURE_ALIASED_1x1, bounds)?;
+
+        let color = if color_run.paletteIndex == FOREGROUND_PALETTE_INDEX {
+            [1., 1., 1., 1.]
+        } else {
+            let run_color = &color_run.runColor;
+            [run_color.r, run_color.g, run_color.b, run_color.a]
+        };
+
+        layers.push(Layer { bounds, coverage, color });
+    }
+
+    Ok(Some(layers))
+}
+
+/// Split a glyph run into its color glyph runs.
+///
+/// Returns `None` if the glyph run has no color layers.
+fn translate_color_glyph_run(
+    settings: &RasterizationSettings,
+    glyph_run: &DWRITE_GLYPH_RUN,
+    palette_index: u32,
+) -> Result<Option<ComPtr<IDWriteColorGlyphRunEnumerator>>, Error> {
+    unsafe {
+        let mut native: *mut IDWriteColorGlyphRunEnumerator = ptr::null_mut();
+        let hr = settings.factory3.TranslateColorGlyphRun(
+            0.0,
+            0.0,
+            glyph_run,
+            ptr::null(),
+            settings.measuring_mode,
+            settings.transform.as_ref().map_or(ptr::null(), |transform| transform),
+            palette_index,
+            &mut native,
+        );
+
+        if hr == DWRITE_E_NOCOLOR {
+            return Ok(None);
+        } else if hr != S_OK || native.is_null() {
+            return Err(Error::from(hr));
+        }
+
+        Ok(Some(ComPtr::from_raw(native)))
+    }
+}
+
+/// Check if a glyph has color layers in the font's `COLR` table.
+pub fn has_color_layers(
+    settings: &RasterizationSettings,
+    face: &FontFace,
+    glyph_index: u16,
+) -> Result<bool, Error> {
+    if !is_color_font(face) {
+        return Ok(false);
+    }
+
+    // The available layers don't depend on the size.
+    let glyph_run = DWRITE_GLYPH_RUN {
+        fontFace: unsafe { face.as_ptr() },
+        fontEmSize: 16.,
+        glyphCount: 1,
+        glyphIndices: &glyph_index,
+        glyphAdvances: &0.0,
+        glyphOffsets: &GlyphOffset::default(),
+        isSideways: 0,
+        bidiLevel: 0,
+    };
+
+    Ok(translate_color_glyph_run(settings, &glyph_run, 0)?.is_some())
+}
+
+/// Check if a font has a `COLR` table with color layers.
+pub fn is_color_font(face: &FontFace) -> bool {
+    unsafe {
+        let mut face2: *mut IDWriteFontFace2 = ptr::null_mut();
+        let hr = (*(face.as_ptr() as *mut IUnknown)).QueryInterface(
+            &IDWriteFontFace2::uuidof(),
+            &mut face2 as *mut *mut IDWriteFontFace2 as *mut *mut c_void,
+        );
+        if hr != S_OK || face2.is_null() {
+            return false;
+        }
+
+        ComPtr::from_raw(face2).IsColorFont() != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpremultiply_pixels() {
+        let mut buffer = [128, 64, 0, 128, 10, 20, 30, 0, 255, 255, 255, 255];
+        unpremultiply(&mut buffer);
+        assert_eq!(buffer, [255, 128, 0, 128, 10, 20, 30, 0, 255, 255, 255, 255]);
+    }
+}
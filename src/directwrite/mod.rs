@@ -1,42 +1,82 @@
 //! Rasterization powered by DirectWrite.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::ops::Deref;
 use std::os::windows::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::sync::OnceLock;
+use std::slice;
+use std::sync::{Arc, Once, OnceLock};
+use std::time::{Duration, Instant};
 
 use log::info;
 use log::debug;
+use log::warn;
 use dwrote::{
-    FontCollection, FontFace, FontFallback, FontStretch, FontStyle, FontWeight, GlyphOffset,
-    GlyphRunAnalysis, TextAnalysisSource, TextAnalysisSourceMethods, DWRITE_GLYPH_RUN,
+    CustomFontCollectionLoaderImpl, FontCollection, FontFace, FontFallback, FontFamily, FontFile,
+    FontSimulations, FontStretch, FontStyle, FontWeight, GlyphOffset, GlyphRunAnalysis,
+    InformationalStringId, TextAnalysisSource, TextAnalysisSourceMethods, DWRITE_GLYPH_RUN,
+    DWRITE_MEASURING_MODE, DWRITE_TEXTURE_TYPE,
 };
 
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::{BOOL, UINT};
 use winapi::shared::ntdef::{HRESULT, LOCALE_NAME_MAX_LENGTH};
 use winapi::shared::winerror::S_OK;
 use winapi::um::dwrite;
-use winapi::um::dwrite::{IDWriteFactory, IDWriteGlyphRunAnalysis, DWRITE_FACTORY_TYPE_SHARED};
+use winapi::um::dwrite::{
+    IDWriteFactory, IDWriteFontFace, IDWriteGlyphRunAnalysis, DWRITE_FACTORY_TYPE_SHARED,
+};
+use winapi::um::dwrite::DWRITE_MATRIX;
 use winapi::um::dwrite_1::{DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE, DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE};
+use winapi::um::dwrite_1::{IDWriteRenderingParams1, DWRITE_TEXT_ANTIALIAS_MODE};
 use winapi::um::dwrite_2::{DWRITE_GRID_FIT_MODE_DISABLED, DWRITE_GRID_FIT_MODE_ENABLED};
+use winapi::um::dwrite_2::DWRITE_GRID_FIT_MODE;
 use winapi::um::dwrite_3::{IDWriteFactory3, DWRITE_RENDERING_MODE1_ALIASED, DWRITE_RENDERING_MODE1_NATURAL_SYMMETRIC};
+use winapi::um::dwrite_3::DWRITE_RENDERING_MODE1;
+use winapi::um::dwrite_3::DWRITE_FONT_AXIS_VALUE;
 use winapi::um::unknwnbase::IUnknown;
 use winapi::um::winnls::GetUserDefaultLocaleName;
+use winapi::um::winuser::{
+    SystemParametersInfoW, FE_FONTSMOOTHINGCLEARTYPE, NONCLIENTMETRICSW, SPI_GETFONTSMOOTHING,
+    SPI_GETFONTSMOOTHINGTYPE, SPI_GETNONCLIENTMETRICS,
+};
 use winapi::Interface;
 use wio::com::ComPtr;
 
+mod bitmap;
+mod cluster;
+mod color;
+mod outline;
+mod shaping;
+mod tables;
+mod tofu;
+
+use tables::VariationGlyph;
+
 use super::{
-    BitmapBuffer, Error, FontDesc, FontKey, GlyphKey, Metrics, RasterizedGlyph, Size, Slant, Style,
-    Weight,
+    BitmapBuffer, Error, FontDesc, FontKey, FontVariation, GlyphKey, Metrics, PathCommand,
+    RasterizedGlyph, Size, Slant, Style, Weight,
 };
 
+/// Shared IDWriteFactory3 instance.
+struct SharedFactory3(ComPtr<IDWriteFactory3>);
+
+// Shared DirectWrite factories are free-threaded and can be used from any thread.
+unsafe impl Send for SharedFactory3 {}
+unsafe impl Sync for SharedFactory3 {}
+
 /// Get or create the IDWriteFactory3 interface for DWrite3 API access.
-fn get_dwrite3_factory() -> Option<*mut IDWriteFactory3> {
-    static FACTORY3: OnceLock<usize> = OnceLock::new();
+fn get_dwrite3_factory() -> Option<ComPtr<IDWriteFactory3>> {
+    static FACTORY3: OnceLock<Option<SharedFactory3>> = OnceLock::new();
 
-    let ptr = *FACTORY3.get_or_init(|| unsafe {
-        // Create a DWrite factory and QueryInterface to IDWriteFactory3.
+    let factory3 = FACTORY3.get_or_init(|| unsafe {
         let mut factory: *mut IDWriteFactory = ptr::null_mut();
         let hr = winapi::um::dwrite::DWriteCreateFactory(
             DWRITE_FACTORY_TYPE_SHARED,
@@ -44,393 +84,3204 @@ fn get_dwrite3_factory() -> Option<*mut IDWriteFactory3> {
             &mut factory as *mut *mut IDWriteFactory as *mut *mut IUnknown,
         );
         if hr != S_OK || factory.is_null() {
-            return 0;
-        }
-
-        let mut factory3: *mut IDWriteFactory3 = ptr::null_mut();
-        let hr = (*(factory as *mut IUnknown)).QueryInterface(
-            &IDWriteFactory3::uuidof(),
-            &mut factory3 as *mut *mut IDWriteFactory3 as *mut *mut std::ffi::c_void,
-        );
-        // Release the original factory reference (QueryInterface adds a ref).
-        (*(factory as *mut IUnknown)).Release();
-
-        if hr != S_OK || factory3.is_null() {
-            return 0;
+            return None;
         }
 
-        factory3 as usize
+        // Releases the original factory reference once the interface has been queried.
+        let factory = ComPtr::from_raw(factory);
+        factory.cast::<IDWriteFactory3>().ok().map(SharedFactory3)
     });
 
-    if ptr == 0 { None } else { Some(ptr as *mut IDWriteFactory3) }
+    factory3.as_ref().map(|factory3| factory3.0.clone())
 }
 
 /// DirectWrite uses 0 for missing glyph symbols.
 /// https://docs.microsoft.com/en-us/typography/opentype/spec/recom#glyph-0-the-notdef-glyph
 const MISSING_GLYPH_INDEX: u16 = 0;
 
+/// Font size in pixels at which stem darkening stops.
+const STEM_DARKENING_MAX_SIZE: f32 = 24.;
+
+/// Variation selector requesting the emoji presentation of a character.
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+
+/// OpenType feature replacing lowercase letters with small capitals.
+const SMALL_CAPS_FEATURE: [u8; 4] = *b"smcp";
+
+/// Family name resolving to the system UI font, like CSS `system-ui`.
+const SYSTEM_UI_FAMILY_NAME: &str = "system-ui";
+
+/// Extensions of font files loaded by `add_fonts_from_dir`.
+const FONT_FILE_EXTENSIONS: [&str; 3] = ["ttf", "otf", "ttc"];
+
 /// Cached DirectWrite font.
+#[derive(Clone)]
 struct Font {
     face: FontFace,
     family_name: String,
     weight: FontWeight,
     style: FontStyle,
     stretch: FontStretch,
+
+    /// Whether the font has color layers, which skips the color path for monochrome fonts.
+    is_color: bool,
+
+    /// Whether the font has glyph outlines, see [`outline::has_outlines`].
+    has_outlines: bool,
+
+    /// Tables of the font's embedded bitmaps, if it has any.
+    bitmap_tables: Option<bitmap::BitmapTables>,
+}
+
+/// DirectWrite parameters used for rasterizing glyph runs.
+struct RasterizationSettings {
+    factory3: ComPtr<IDWriteFactory3>,
+    rendering_mode: DWRITE_RENDERING_MODE1,
+    measuring_mode: DWRITE_MEASURING_MODE,
+    grid_fit_mode: DWRITE_GRID_FIT_MODE,
+    grid_fit_threshold: Option<f32>,
+    antialias_mode: DWRITE_TEXT_ANTIALIAS_MODE,
+    texture_type: DWRITE_TEXTURE_TYPE,
+    transform: Option<DWRITE_MATRIX>,
+
+    /// Rendering mode whose coverage adjustments apply to rasterized glyphs.
+    coverage_mode: super::RenderingMode,
+}
+
+impl RasterizationSettings {
+    /// Check if glyphs stay aligned to the pixel grid, which is required for grid fitting and
+    /// subpixel rendering.
+    fn is_axis_aligned(&self) -> bool {
+        self.transform.map_or(true, |transform| transform.m12 == 0. && transform.m21 == 0.)
+    }
+
+    /// Enable or disable grid fitting for all sizes, if the transform allows it.
+    fn set_grid_fitting(&mut self, grid_fitting: bool) {
+        self.grid_fit_threshold = None;
+        self.grid_fit_mode = if grid_fitting && self.is_axis_aligned() {
+            DWRITE_GRID_FIT_MODE_ENABLED
+        } else {
+            DWRITE_GRID_FIT_MODE_DISABLED
+        };
+    }
+
+    /// Get the grid fitting used for glyphs of the specified size.
+    fn grid_fit_mode(&self, em_size: f32) -> DWRITE_GRID_FIT_MODE {
+        match self.grid_fit_threshold {
+            Some(threshold) if em_size <= threshold && self.is_axis_aligned() => {
+                DWRITE_GRID_FIT_MODE_ENABLED
+            },
+            Some(_) => DWRITE_GRID_FIT_MODE_DISABLED,
+            None => self.grid_fit_mode,
+        }
+    }
 }
 
+/// Rasterizes glyphs using DirectWrite.
+///
+/// All DirectWrite objects are created from a shared factory, which makes them free-threaded and
+/// independent of the COM apartment of the calling thread. So the rasterizer can be moved to and
+/// used from any thread, but it is not `Sync`, since the font handles lazily cache interfaces
+/// without any synchronization.
 pub struct DirectWriteRasterizer {
     fonts: HashMap<FontKey, Font>,
     keys: HashMap<FontDesc, FontKey>,
     available_fonts: FontCollection,
+    custom_fonts: Vec<FontCollection>,
     fallback_sequence: Option<FontFallback>,
+    fallback_enabled: bool,
+    last_resort_font: Option<Font>,
+    replacement_char_fallback: bool,
+    advance_rounding: AdvanceRounding,
+    stats: Option<RefCell<RasterStats>>,
     rendering_mode: super::RenderingMode,
+    background_opaque: Option<bool>,
     grid_fitting: bool,
+    grid_fit_threshold: Option<f32>,
+    fuzzy_family_matching: bool,
+    line_height_scale: f32,
+    metrics_override: Option<MetricsOverride>,
+    missing_glyph_policy: MissingGlyphPolicy,
+    notdef_index: u16,
+    line_metrics_source: LineMetricsSource,
+    antialias_mode: AntialiasMode,
+    measuring_mode: MeasuringMode,
+    transform: Option<[f32; 6]>,
+    vertical: bool,
+    color_palette: u32,
+    stem_darkening: Option<f32>,
+    synthetic_smallcaps: Option<f32>,
+    grayscale_params: RenderingParams,
+    cleartype_params: RenderingParams,
+    display_params: DisplayParams,
+    pixel_geometry: PixelGeometry,
+    linear_coverage: bool,
+    glyph_padding: u32,
+    premultiply_alpha: bool,
+    mono_output: bool,
+    mono_threshold: u8,
+    metrics_cache: RefCell<HashMap<(FontKey, Size), Metrics>>,
+    fallback_cache: RefCell<FallbackCache>,
+    locale: String,
 }
 
-impl DirectWriteRasterizer {
-    fn rasterize_glyph(
-        &self,
-        face: &FontFace,
-        size: Size,
-        character: char,
-        glyph_index: u16,
-    ) -> Result<RasterizedGlyph, Error> {
-        let em_size = size.as_px();
+/// System fallback fonts resolved for characters missing from a loaded font.
+#[derive(Default)]
+struct FallbackCache {
+    /// Fallback font by primary font, character and variation selector.
+    fonts: HashMap<(FontKey, char, Option<char>), Option<Font>>,
+}
 
-        let glyph_run = DWRITE_GLYPH_RUN {
-            fontFace: unsafe { face.as_ptr() },
-            fontEmSize: em_size,
-            glyphCount: 1,
-            glyphIndices: &glyph_index,
-            glyphAdvances: &0.0,
-            glyphOffsets: &GlyphOffset::default(),
-            isSideways: 0,
-            bidiLevel: 0,
-        };
+/// Adjustments applied on top of the metrics reported by the font.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct MetricsOverride {
+    /// Fixed cell width in pixels, replacing the font's average advance.
+    pub average_advance: Option<f64>,
 
-        let (rendering_mode1, measuring_mode, antialias_mode) = match self.rendering_mode {
-            super::RenderingMode::Aliased => (
-                DWRITE_RENDERING_MODE1_ALIASED,
-                dwrote::DWRITE_MEASURING_MODE_GDI_CLASSIC,
-                DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
-            ),
-            super::RenderingMode::Grayscale => (
-                DWRITE_RENDERING_MODE1_NATURAL_SYMMETRIC,
-                dwrote::DWRITE_MEASURING_MODE_NATURAL,
-                DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
-            ),
-            super::RenderingMode::Subpixel => (
-                DWRITE_RENDERING_MODE1_NATURAL_SYMMETRIC,
-                dwrote::DWRITE_MEASURING_MODE_NATURAL,
-                DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE,
-            ),
-        };
+    /// Fixed line height in pixels, replacing the font's line height.
+    pub line_height: Option<f64>,
+}
 
-        let grid_fit_mode = if self.grid_fitting {
-            DWRITE_GRID_FIT_MODE_ENABLED
-        } else {
-            DWRITE_GRID_FIT_MODE_DISABLED
-        };
+/// Glyph rendered for characters which are missing from all fonts.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MissingGlyphPolicy {
+    /// The font's `.notdef` glyph, usually an empty box.
+    #[default]
+    NotdefGlyph,
 
-        let factory3 = get_dwrite3_factory()
-            .ok_or_else(|| Error::PlatformError("IDWriteFactory3 not available".into()))?;
-
-        let glyph_analysis = unsafe {
-            let mut native: *mut IDWriteGlyphRunAnalysis = ptr::null_mut();
-            let hr = (*factory3).CreateGlyphRunAnalysis(
-                &glyph_run as *const DWRITE_GLYPH_RUN,
-                ptr::null(),
-                rendering_mode1,
-                measuring_mode,
-                grid_fit_mode,
-                antialias_mode,
-                0.0,
-                0.0,
-                &mut native,
-            );
-            if hr != S_OK || native.is_null() {
-                info!("DWrite3 CreateGlyphRunAnalysis failed: hr={:X}", hr);
-                return Err(Error::from(hr));
-            }
-            GlyphRunAnalysis::take(ComPtr::from_raw(native))
-        };
+    /// Nothing, only the advance is preserved.
+    Blank,
 
-        let texture_type = match self.rendering_mode {
-            super::RenderingMode::Subpixel => dwrote::DWRITE_TEXTURE_CLEARTYPE_3x1,
-            _ => dwrote::DWRITE_TEXTURE_ALIASED_1x1,
-        };
+    /// A box containing the character's codepoint in hexadecimal.
+    HexBox,
+}
 
-        let bounds = glyph_analysis.get_alpha_texture_bounds(texture_type)?;
+/// Font table providing the ascent, descent and line gap.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LineMetricsSource {
+    /// Horizontal header (`hhea`) metrics.
+    Hhea,
 
-        let raw_buffer = glyph_analysis.create_alpha_texture(texture_type, bounds)?;
+    /// Typographic metrics of the `OS/2` table.
+    TypoOs2,
 
-        let buffer = match self.rendering_mode {
-            super::RenderingMode::Subpixel => {
-                // ClearType 3x1: raw RGB subpixel data, pass through directly.
-                BitmapBuffer::Rgb(raw_buffer)
-            },
-            _ => {
-                // Aliased and Grayscale both use ALIASED_1x1: single-channel alpha.
-                // Expand to RGB for the glyph atlas.
-                let mut rgb = Vec::with_capacity(raw_buffer.len() * 3);
-                for &alpha in &raw_buffer {
-                    rgb.push(alpha);
-                    rgb.push(alpha);
-                    rgb.push(alpha);
-                }
-                BitmapBuffer::Rgb(rgb)
-            },
-        };
+    /// Typographic metrics if the font sets the `USE_TYPO_METRICS` flag, `hhea` otherwise.
+    #[default]
+    Auto,
+}
 
-        Ok(RasterizedGlyph {
-            character,
-            width: bounds.right - bounds.left,
-            height: bounds.bottom - bounds.top,
-            top: -bounds.top,
-            left: bounds.left,
-            advance: (0, 0),
-            buffer,
-        })
-    }
+/// Antialiasing of rasterized glyphs.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AntialiasMode {
+    /// Antialiasing matching the [`crate::RenderingMode`].
+    #[default]
+    Automatic,
 
-    fn get_loaded_font(&self, font_key: FontKey) -> Result<&Font, Error> {
-        self.fonts.get(&font_key).ok_or(Error::UnknownFontKey)
-    }
+    /// Grayscale antialiasing.
+    Grayscale,
 
-    fn get_glyph_index(&self, face: &FontFace, character: char) -> u16 {
-        face.glyph_indices(&[character as u32])
-            .ok()
-            .and_then(|v| v.first().copied())
-            .unwrap_or(MISSING_GLYPH_INDEX)
-    }
+    /// ClearType subpixel antialiasing.
+    ClearType,
 
-    fn get_fallback_font(&self, loaded_font: &Font, character: char) -> Option<dwrote::Font> {
-        let fallback = self.fallback_sequence.as_ref()?;
+    /// No antialiasing, every pixel is either fully covered or empty.
+    None,
+}
 
-        let mut buffer = [0u16; 2];
-        character.encode_utf16(&mut buffer);
+/// Glyph measuring mode used for positioning glyphs.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MeasuringMode {
+    /// GDI classic metrics for aliased rendering, natural metrics otherwise.
+    #[default]
+    Automatic,
 
-        let length = character.len_utf16() as u32;
-        let utf16_codepoints = &buffer[..length as usize];
+    /// Natural metrics, independent of the display resolution.
+    Natural,
 
-        let locale = get_current_locale();
+    /// GDI compatible metrics, like classic Win32 controls.
+    GdiClassic,
 
-        let text_analysis_source_data = TextAnalysisSourceData { locale: &locale, length };
-        let text_analysis_source = TextAnalysisSource::from_text(
-            Box::new(text_analysis_source_data),
-            Cow::Borrowed(utf16_codepoints),
-        );
+    /// GDI compatible metrics with natural advances.
+    GdiNatural,
+}
 
-        let fallback_result = fallback.map_characters(
-            &text_analysis_source,
-            0,
-            length,
-            &self.available_fonts,
-            Some(&loaded_font.family_name),
-            loaded_font.weight,
-            loaded_font.style,
-            loaded_font.stretch,
-        );
+/// Physical arrangement of the display's subpixels, used for ClearType rendering.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PixelGeometry {
+    /// Geometry of the rendering params, see
+    /// [`DirectWriteRasterizer::set_native_rendering_params`].
+    #[default]
+    Automatic,
 
-        fallback_result.mapped_font
-    }
+    /// No subpixels, like on pentile or rotated displays, ClearType falls back to grayscale.
+    Flat,
+
+    /// Horizontal red, green and blue subpixels.
+    Rgb,
+
+    /// Horizontal blue, green and red subpixels.
+    Bgr,
 }
 
-impl crate::Rasterize for DirectWriteRasterizer {
-    fn new() -> Result<DirectWriteRasterizer, Error> {
-        Ok(DirectWriteRasterizer {
-            fonts: HashMap::new(),
-            keys: HashMap::new(),
-            available_fonts: FontCollection::system(),
-            fallback_sequence: FontFallback::get_system_fallback(),
-            rendering_mode: Default::default(),
-            grid_fitting: false,
-        })
-    }
+/// Rounding of glyph advances to whole pixels.
+///
+/// Glyphs are always rasterized at whole pixel positions, so the rounded advances keep glyphs on
+/// the pixel grid. Callers doing their own subpixel positioning should use
+/// [`AdvanceRounding::None`] and the fractional advances, for example from
+/// [`DirectWriteRasterizer::glyph_bounds`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AdvanceRounding {
+    /// Keep fractional advances, integer advances are truncated.
+    None,
 
-    fn set_rendering_mode(&mut self, mode: super::RenderingMode) {
-        self.rendering_mode = mode;
-    }
+    /// Round to the nearest pixel.
+    #[default]
+    Round,
 
-    fn set_grid_fitting(&mut self, enabled: bool) {
-        self.grid_fitting = enabled;
+    /// Round down.
+    Floor,
+
+    /// Round up.
+    Ceil,
+}
+
+impl AdvanceRounding {
+    fn apply(self, advance: f32) -> f32 {
+        match self {
+            AdvanceRounding::None => advance,
+            AdvanceRounding::Round => advance.round(),
+            AdvanceRounding::Floor => advance.floor(),
+            AdvanceRounding::Ceil => advance.ceil(),
+        }
     }
+}
 
-    fn metrics(&self, key: FontKey, size: Size) -> Result<Metrics, Error> {
-        let face = &self.get_loaded_font(key)?.face;
-        let vmetrics = face.metrics().metrics0();
+/// Actual style of a loaded font, which can differ from the requested style.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedStyle {
+    /// Weight category, fonts with a weight of at least `600` are bold.
+    pub weight: Weight,
 
-        let scale = f64::from(size.as_px()) / f64::from(vmetrics.designUnitsPerEm);
+    /// Numeric weight, like `400` for regular and `700` for bold fonts.
+    pub weight_value: u32,
 
-        let underline_position = f64::from(vmetrics.underlinePosition) * scale;
-        let underline_thickness = f64::from(vmetrics.underlineThickness) * scale;
+    pub slant: Slant,
 
-        let strikeout_position = f64::from(vmetrics.strikethroughPosition) * scale;
-        let strikeout_thickness = f64::from(vmetrics.strikethroughThickness) * scale;
+    /// Numeric stretch from `1` for ultra-condensed to `9` for ultra-expanded fonts, with `5`
+    /// being normal.
+    pub stretch: u32,
+}
 
-        let ascent = f64::from(vmetrics.ascent) * scale;
-        let descent = -f64::from(vmetrics.descent) * scale;
-        let line_gap = f64::from(vmetrics.lineGap) * scale;
+impl ResolvedStyle {
+    fn new(weight: FontWeight, style: FontStyle, stretch: FontStretch) -> Self {
+        let weight_value = weight.to_u32();
 
-        let line_height = ascent - descent + line_gap;
+        Self {
+            weight: if weight_value >= 600 { Weight::Bold } else { Weight::Normal },
+            weight_value,
+            slant: style.into(),
+            stretch: stretch.to_u32(),
+        }
+    }
+}
 
-        // Since all monospace characters have the same width, we use `!` for horizontal metrics.
-        let character = '!';
-        let glyph_index = self.get_glyph_index(face, character);
+/// Face of a font family.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FaceInfo {
+    /// Name of the face inside its family, like `Bold Italic`.
+    pub name: String,
 
-        let glyph_metrics = face.design_glyph_metrics(&[glyph_index], false)
-            .map_err(|_| Error::MetricsNotFound)?;
-        let hmetrics = glyph_metrics.first().ok_or(Error::MetricsNotFound)?;
+    pub style: ResolvedStyle,
+}
 
-        let average_advance = f64::from(hmetrics.advanceWidth) * scale;
+/// Descriptive strings from a font's naming table.
+///
+/// See [`DirectWriteRasterizer::font_info`].
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct FontInfo {
+    pub copyright: Option<String>,
+    pub family_name: Option<String>,
+    pub full_name: Option<String>,
+    pub version: Option<String>,
+    pub designer: Option<String>,
+    pub license: Option<String>,
+    pub license_url: Option<String>,
+}
 
-        debug!(
-            "crossfont metrics: designUnitsPerEm={}, size_px={}, scale={}, advanceWidth={}, \
-             average_advance={}, ascent={}, descent={}, line_gap={}, line_height={}",
-            vmetrics.designUnitsPerEm,
-            size.as_px(),
-            scale,
-            hmetrics.advanceWidth,
-            average_advance,
-            ascent,
-            descent,
-            line_gap,
-            line_height,
-        );
+/// Font which renders the start of a text.
+///
+/// See [`DirectWriteRasterizer::resolve_font_for`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedFontInfo {
+    pub family_name: String,
 
-        Ok(Metrics {
-            descent: descent as f32,
-            average_advance,
-            line_height,
-            underline_position: underline_position as f32,
-            underline_thickness: underline_thickness as f32,
-            strikeout_position: strikeout_position as f32,
-            strikeout_thickness: strikeout_thickness as f32,
-        })
-    }
+    pub style: ResolvedStyle,
 
-    fn load_font(&mut self, desc: &FontDesc, _size: Size) -> Result<FontKey, Error> {
-        // Fast path if face is already loaded.
-        if let Some(key) = self.keys.get(desc) {
-            return Ok(*key);
-        }
+    /// Whether a system fallback font is used instead of the requested font.
+    pub is_fallback: bool,
 
-        let family = self
-            .available_fonts
-            .font_family_by_name(&desc.name)
-            .ok()
-            .flatten()
-            .ok_or_else(|| Error::FontNotFound(desc.clone()))?;
+    /// Whether the font has a glyph for the character.
+    pub has_glyph: bool,
+}
 
-        let font = match desc.style {
-            Style::Description { weight, slant } => {
-                // This searches for the "best" font - should mean we don't have to worry about
-                // fallbacks if our exact desired weight/style isn't available.
-                family
-                    .first_matching_font(weight.into(), FontStretch::Normal, slant.into())
-                    .map_err(|_| Error::FontNotFound(desc.clone()))
-            },
-            Style::Specific(ref style) => {
-                let count = family.get_font_count();
+/// Named instance of a variable font, like `SemiBold Italic`.
+///
+/// See [`DirectWriteRasterizer::named_instances`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    pub name: String,
 
-                (0..count)
-                    .find_map(|idx| {
-                        family.font(idx).ok().filter(|f| f.face_name() == *style)
-                    })
-                    .ok_or_else(|| Error::FontNotFound(desc.clone()))
-            },
-        }?;
+    /// Coordinates of the instance on every axis of the font.
+    pub coordinates: Vec<FontVariation>,
+}
 
-        let key = FontKey::next();
-        self.keys.insert(desc.clone(), key);
-        self.fonts.insert(key, font.into());
+/// Rasterization statistics for profiling.
+///
+/// See [`DirectWriteRasterizer::enable_stats`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct RasterStats {
+    /// Lookups of a character's glyph index in a font.
+    pub glyph_index_lookups: StageStats,
 
-        Ok(key)
-    }
+    /// Searches for a system fallback font.
+    pub fallback_lookups: StageStats,
 
-    fn get_glyph(&mut self, glyph: GlyphKey) -> Result<RasterizedGlyph, Error> {
-        let loaded_font = self.get_loaded_font(glyph.font_key)?;
-
-        let loaded_fallback_font;
-        let mut font = loaded_font;
-        let mut glyph_index = self.get_glyph_index(&loaded_font.face, glyph.character);
-        if glyph_index == MISSING_GLYPH_INDEX {
-            if let Some(fallback_font) = self.get_fallback_font(loaded_font, glyph.character) {
-                loaded_fallback_font = Font::from(fallback_font);
-                glyph_index = self.get_glyph_index(&loaded_fallback_font.face, glyph.character);
-                font = &loaded_fallback_font;
-            }
-        }
+    /// Creation of DirectWrite glyph run analyses, which rasterize the glyphs.
+    pub glyph_run_analyses: StageStats,
 
-        let rasterized_glyph =
-            self.rasterize_glyph(&font.face, glyph.size, glyph.character, glyph_index)?;
+    /// Copies of rasterized glyphs out of DirectWrite.
+    pub texture_copies: StageStats,
+}
 
-        if glyph_index == MISSING_GLYPH_INDEX {
-            Err(Error::MissingGlyph(rasterized_glyph))
-        } else {
-            Ok(rasterized_glyph)
-        }
-    }
+/// Number of calls and total time spent in a rasterization stage.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StageStats {
+    pub count: u64,
+    pub duration: Duration,
+}
 
-    fn kerning(&mut self, _left: GlyphKey, _right: GlyphKey) -> (f32, f32) {
-        (0., 0.)
-    }
+/// Coverage adjustments for a [`crate::RenderingMode`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderingParams {
+    /// Gamma for blending glyphs with the background.
+    ///
+    /// Gamma correction depends on the text color, so it is not applied to the rasterized
+    /// coverage and has to be handled while blending. It is used to linearize the coverage, see
+    /// [`DirectWriteRasterizer::set_linear_coverage`].
+    pub gamma: f32,
+
+    /// Contrast enhancement applied to the glyph coverage, `0.0` disables it.
+    pub enhanced_contrast: f32,
 }
 
-impl From<dwrote::Font> for Font {
-    fn from(font: dwrote::Font) -> Font {
-        Font {
-            face: font.create_font_face(),
-            family_name: font.family_name(),
-            weight: font.weight(),
-            style: font.style(),
-            stretch: font.stretch(),
-        }
-    }
+impl RenderingParams {
+    /// Parameters for aliased rendering, which has no partial coverage to adjust.
+    const ALIASED: Self = Self { gamma: 1., enhanced_contrast: 0. };
+    /// DirectWrite's default parameters for ClearType antialiasing.
+    pub const CLEARTYPE: Self = Self { gamma: 1.8, enhanced_contrast: 0.5 };
+    /// DirectWrite's default parameters for grayscale antialiasing.
+    pub const GRAYSCALE: Self = Self { gamma: 1.8, enhanced_contrast: 1. };
 }
 
-impl From<Weight> for FontWeight {
-    fn from(weight: Weight) -> FontWeight {
-        match weight {
-            Weight::Bold => FontWeight::Bold,
-            Weight::Normal => FontWeight::Regular,
+/// Display specific parameters of a DirectWrite rendering params object.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct DisplayParams {
+    cleartype_level: f32,
+    pixel_geometry: dwrite::DWRITE_PIXEL_GEOMETRY,
+
+    /// Rendering mode replacing the one picked for the [`crate::RenderingMode`].
+    rendering_mode: Option<DWRITE_RENDERING_MODE1>,
+}
+
+impl Default for DisplayParams {
+    fn default() -> Self {
+        Self {
+            cleartype_level: 1.,
+            pixel_geometry: dwrite::DWRITE_PIXEL_GEOMETRY_RGB,
+            rendering_mode: None,
         }
     }
 }
 
-impl From<Slant> for FontStyle {
-    fn from(slant: Slant) -> FontStyle {
-        match slant {
-            Slant::Oblique => FontStyle::Oblique,
-            Slant::Italic => FontStyle::Italic,
-            Slant::Normal => FontStyle::Normal,
+impl DisplayParams {
+    /// Adjust ClearType coverage to the ClearType level and subpixel order of the display.
+    fn apply(&self, coverage: &mut [u8]) {
+        let level = match self.pixel_geometry {
+            dwrite::DWRITE_PIXEL_GEOMETRY_FLAT => 0.,
+            _ => self.cleartype_level.clamp(0., 1.),
+        };
+        let is_bgr = self.pixel_geometry == dwrite::DWRITE_PIXEL_GEOMETRY_BGR;
+
+        if level == 1. && !is_bgr {
+            return;
+        }
+
+        for pixel in coverage.chunks_exact_mut(3) {
+            if is_bgr {
+                pixel.swap(0, 2);
+            }
+
+            // Blend the subpixel coverage towards grayscale for lower ClearType levels.
+            let gray = pixel.iter().map(|&value| f32::from(value)).sum::<f32>() / 3.;
+            for value in pixel {
+                *value = (gray + level * (f32::from(*value) - gray)).round() as u8;
+            }
         }
     }
 }
 
-fn get_current_locale() -> String {
-    let mut buffer = vec![0u16; LOCALE_NAME_MAX_LENGTH];
-    let len =
-        unsafe { GetUserDefaultLocaleName(buffer.as_mut_ptr(), buffer.len() as i32) as usize };
+/// Options overriding the rasterizer's configuration for a single glyph.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RasterizeOptions {
+    /// Enable or disable grid fitting, instead of using [`crate::Rasterize::set_grid_fitting`].
+    pub grid_fitting: Option<bool>,
 
-    // `len` includes null byte, which we don't need in Rust.
-    OsString::from_wide(&buffer[..len - 1]).into_string().expect("Locale not valid unicode")
+    /// Rendering mode used instead of the one of [`crate::Rasterize::set_rendering_mode`].
+    ///
+    /// This allows rendering glyphs like box-drawing characters without antialiasing, while
+    /// other text is antialiased. With [`DirectWriteRasterizer::set_mono_output`], glyphs are
+    /// only packed into [`BitmapBuffer::Bitmap1`] buffers if this rendering mode is aliased.
+    pub rendering_mode: Option<super::RenderingMode>,
 }
 
-/// Font fallback information for dwrote's TextAnalysisSource.
-struct TextAnalysisSourceData<'a> {
-    locale: &'a str,
-    length: u32,
-}
+/// Font-wide metrics for vertical text layout.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct VerticalMetrics {
+    /// Distance from the vertical baseline to the right edge of the line.
+    pub ascent: f32,
 
-impl TextAnalysisSourceMethods for TextAnalysisSourceData<'_> {
-    fn get_locale_name(&self, _text_position: u32) -> (Cow<'_, str>, u32) {
-        (Cow::Borrowed(self.locale), self.length)
-    }
+    /// Distance from the vertical baseline to the left edge of the line.
+    ///
+    /// This is negative like [`Metrics::descent`].
+    pub descent: f32,
 
-    fn get_paragraph_reading_direction(&self) -> dwrite::DWRITE_READING_DIRECTION {
-        dwrite::DWRITE_READING_DIRECTION_LEFT_TO_RIGHT
-    }
+    /// Additional spacing between vertical lines.
+    pub line_gap: f32,
+}
+
+/// Ink extents of a glyph in pixels.
+///
+/// All values are relative to the glyph origin on the baseline, with the Y axis pointing up like
+/// [`RasterizedGlyph::top`].
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GlyphBounds {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+
+    /// Horizontal advance of the glyph.
+    pub advance: f32,
+}
+
+/// Unscaled metrics of a glyph in font design units.
+///
+/// See [`DirectWriteRasterizer::units_per_em`] for converting them to pixels.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct RawGlyphMetrics {
+    pub advance_width: u32,
+    pub advance_height: u32,
+    pub left_side_bearing: i32,
+    pub right_side_bearing: i32,
+    pub top_side_bearing: i32,
+    pub bottom_side_bearing: i32,
+
+    /// Y coordinate of the glyph's vertical origin.
+    pub vertical_origin_y: i32,
+}
+
+/// Rasterized glyph of a shaped string.
+#[derive(Debug, Clone)]
+pub struct ShapedGlyph {
+    /// Index of the glyph in the font.
+    pub glyph_index: u16,
+
+    /// Byte offset in the source text of the cluster this glyph belongs to.
+    ///
+    /// Multiple glyphs can share a cluster, and a single glyph can cover multiple characters
+    /// like with ligatures.
+    pub cluster: usize,
+
+    /// Horizontal advance in pixels.
+    pub advance: f32,
+
+    /// Offset from the pen position in pixels, with the Y axis pointing up.
+    pub offset: (f32, f32),
+
+    /// Rasterized bitmap of the glyph.
+    pub glyph: RasterizedGlyph,
+}
+
+impl DirectWriteRasterizer {
+    /// Get the rendering mode used for rasterization.
+    ///
+    /// Subpixel rendering is replaced by grayscale anti-aliasing unless the background is
+    /// known to be opaque.
+    fn effective_rendering_mode(&self) -> super::RenderingMode {
+        self.resolve_rendering_mode(self.rendering_mode)
+    }
+
+    /// Resolve a rendering mode, like [`Self::effective_rendering_mode`] does for the global mode.
+    fn resolve_rendering_mode(&self, mode: super::RenderingMode) -> super::RenderingMode {
+        match (mode, self.background_opaque) {
+            (super::RenderingMode::Auto, Some(true)) => super::RenderingMode::Subpixel,
+            (super::RenderingMode::Auto, _) | (super::RenderingMode::Subpixel, Some(false)) => {
+                super::RenderingMode::Grayscale
+            },
+            (mode, _) => mode,
+        }
+    }
+
+    /// Get the rendering mode whose coverage adjustments apply to glyphs rendered in `mode`.
+    fn coverage_mode(&self, mode: super::RenderingMode) -> super::RenderingMode {
+        match self.antialias_mode {
+            AntialiasMode::Automatic => mode,
+            AntialiasMode::Grayscale => super::RenderingMode::Grayscale,
+            AntialiasMode::ClearType => super::RenderingMode::Subpixel,
+            AntialiasMode::None => super::RenderingMode::Aliased,
+        }
+    }
+
+    /// Resolve the DirectWrite parameters for the current rendering configuration.
+    fn rasterization_settings(&self) -> Result<RasterizationSettings, Error> {
+        self.rasterization_settings_for(self.rendering_mode)
+    }
+
+    /// Resolve the DirectWrite parameters for rendering in `mode` instead of the global mode.
+    fn rasterization_settings_for(
+        &self,
+        mode: super::RenderingMode,
+    ) -> Result<RasterizationSettings, Error> {
+        let mode = self.resolve_rendering_mode(mode);
+        let (rendering_mode, measuring_mode, antialias_mode) = match mode {
+            // DirectWrite has no aliased antialiasing mode, the aliased rendering mode already
+            // disables antialiasing and the texture is thresholded after rasterization.
+            super::RenderingMode::Aliased => (
+                DWRITE_RENDERING_MODE1_ALIASED,
+                dwrote::DWRITE_MEASURING_MODE_GDI_CLASSIC,
+                DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+            ),
+            super::RenderingMode::Grayscale => (
+                DWRITE_RENDERING_MODE1_NATURAL_SYMMETRIC,
+                dwrote::DWRITE_MEASURING_MODE_NATURAL,
+                DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+            ),
+            super::RenderingMode::Subpixel | super::RenderingMode::Auto => (
+                DWRITE_RENDERING_MODE1_NATURAL_SYMMETRIC,
+                dwrote::DWRITE_MEASURING_MODE_NATURAL,
+                DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE,
+            ),
+        };
+
+        // Aliased rendering relies on the aliased rendering mode for its hard edges.
+        let rendering_mode = match self.display_params.rendering_mode {
+            Some(display_mode) if mode != super::RenderingMode::Aliased => display_mode,
+            _ => rendering_mode,
+        };
+
+        let measuring_mode = match self.measuring_mode {
+            MeasuringMode::Automatic => measuring_mode,
+            MeasuringMode::Natural => dwrote::DWRITE_MEASURING_MODE_NATURAL,
+            MeasuringMode::GdiClassic => dwrote::DWRITE_MEASURING_MODE_GDI_CLASSIC,
+            MeasuringMode::GdiNatural => dwrote::DWRITE_MEASURING_MODE_GDI_NATURAL,
+        };
+
+        // The aliased rendering mode has no ClearType textures, DirectWrite reports empty bounds
+        // for them.
+        let is_aliased = rendering_mode == DWRITE_RENDERING_MODE1_ALIASED;
+        let antialias_mode = match self.antialias_mode {
+            AntialiasMode::Automatic => antialias_mode,
+            AntialiasMode::ClearType if is_aliased => DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+            AntialiasMode::Grayscale | AntialiasMode::None => DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+            AntialiasMode::ClearType => DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE,
+        };
+
+        let texture_type = if antialias_mode == DWRITE_TEXT_ANTIALIAS_MODE_CLEARTYPE {
+            dwrote::DWRITE_TEXTURE_CLEARTYPE_3x1
+        } else {
+            dwrote::DWRITE_TEXTURE_ALIASED_1x1
+        };
+
+        let transform = self.transform.map(|[m11, m12, m21, m22, dx, dy]| DWRITE_MATRIX {
+            m11,
+            m12,
+            m21,
+            m22,
+            dx,
+            dy,
+        });
+
+        let factory3 = get_dwrite3_factory().ok_or(Error::Unsupported("IDWriteFactory3"))?;
+
+        let mut settings = RasterizationSettings {
+            factory3,
+            rendering_mode,
+            measuring_mode,
+            grid_fit_mode: DWRITE_GRID_FIT_MODE_DISABLED,
+            grid_fit_threshold: None,
+            antialias_mode,
+            texture_type,
+            transform,
+            coverage_mode: if is_aliased {
+                super::RenderingMode::Aliased
+            } else {
+                self.coverage_mode(mode)
+            },
+        };
+        settings.set_grid_fitting(self.grid_fitting);
+        settings.grid_fit_threshold = self.grid_fit_threshold;
+
+        // Subpixel order doesn't match the pixel grid anymore with rotated glyphs.
+        if !settings.is_axis_aligned() {
+            settings.antialias_mode = DWRITE_TEXT_ANTIALIAS_MODE_GRAYSCALE;
+            settings.texture_type = dwrote::DWRITE_TEXTURE_ALIASED_1x1;
+        }
+
+        Ok(settings)
+    }
+
+    fn rasterize_glyph(
+        &self,
+        settings: &RasterizationSettings,
+        font: &Font,
+        size: Size,
+        character: char,
+        glyph_index: u16,
+    ) -> Result<RasterizedGlyph, Error> {
+        let face = &font.face;
+        let em_size = em_size(size)?;
+
+        let glyph_run = DWRITE_GLYPH_RUN {
+            fontFace: unsafe { face.as_ptr() },
+            fontEmSize: em_size,
+            glyphCount: 1,
+            glyphIndices: &glyph_index,
+            glyphAdvances: &0.0,
+            glyphOffsets: &GlyphOffset::default(),
+            isSideways: 0,
+            bidiLevel: 0,
+        };
+
+        let (advance, side_bearings) =
+            glyph_metrics(face, glyph_index, em_size, self.vertical, self.advance_rounding)?;
+
+        if font.is_color {
+            let palette_index = self.color_palette_index(face);
+            if let Some(rasterized_glyph) = color::rasterize_color_glyph(
+                settings,
+                &glyph_run,
+                palette_index,
+                character,
+                advance,
+            )? {
+                return Ok(RasterizedGlyph { side_bearings, ..rasterized_glyph });
+            }
+        }
+
+        if let Some(rasterized_glyph) =
+            bitmap::rasterize_embedded_bitmap(font, glyph_index, em_size, character, advance)?
+        {
+            return Ok(RasterizedGlyph { side_bearings, ..rasterized_glyph });
+        }
+
+        let glyph_analysis = self.record(
+            |stats| &mut stats.glyph_run_analyses,
+            || glyph_run_analysis(settings, &glyph_run, settings.antialias_mode, (0., 0.)),
+        )?;
+
+        let bounds = glyph_analysis.get_alpha_texture_bounds(settings.texture_type)?;
+
+        // Glyphs without coverage, like whitespace, have no texture to create.
+        if bounds.right <= bounds.left || bounds.bottom <= bounds.top {
+            return Ok(RasterizedGlyph {
+                character,
+                advance,
+                side_bearings,
+                buffer: BitmapBuffer::Rgb(Vec::new()),
+                ..Default::default()
+            });
+        }
+
+        let mut raw_buffer = self.record(
+            |stats| &mut stats.texture_copies,
+            || glyph_analysis.create_alpha_texture(settings.texture_type, bounds),
+        )?;
+
+        if let Some(amount) = self.stem_darkening {
+            darken_stems(&mut raw_buffer, em_size, amount);
+        }
+
+        let params = self.rendering_params(settings.coverage_mode);
+        enhance_contrast(&mut raw_buffer, params.enhanced_contrast);
+
+        // Guarantee hard edges, even for partially covered pixels from transformed glyphs. Packed
+        // monochrome output is thresholded with the mono threshold when the glyph is finished.
+        if self.is_aliased(settings) && !self.mono_output {
+            for value in &mut raw_buffer {
+                *value = if *value >= 0x80 { 0xFF } else { 0x00 };
+            }
+        }
+
+        if settings.texture_type == dwrote::DWRITE_TEXTURE_CLEARTYPE_3x1 {
+            // ClearType 3x1: raw RGB subpixel data, adjusted for the display.
+            let pixel_geometry = match self.pixel_geometry {
+                PixelGeometry::Automatic => self.display_params.pixel_geometry,
+                PixelGeometry::Flat => dwrite::DWRITE_PIXEL_GEOMETRY_FLAT,
+                PixelGeometry::Rgb => dwrite::DWRITE_PIXEL_GEOMETRY_RGB,
+                PixelGeometry::Bgr => dwrite::DWRITE_PIXEL_GEOMETRY_BGR,
+            };
+            DisplayParams { pixel_geometry, ..self.display_params }.apply(&mut raw_buffer);
+        }
+
+        if self.linear_coverage {
+            linearize_coverage(&mut raw_buffer, params.gamma);
+        }
+
+        let buffer = match settings.texture_type {
+            // ClearType 3x1 is already RGB.
+            dwrote::DWRITE_TEXTURE_CLEARTYPE_3x1 => BitmapBuffer::Rgb(raw_buffer),
+            _ => {
+                // Aliased and Grayscale both use ALIASED_1x1: single-channel alpha.
+                // Expand to RGB for the glyph atlas.
+                let mut rgb = Vec::with_capacity(raw_buffer.len() * 3);
+                for &alpha in &raw_buffer {
+                    rgb.push(alpha);
+                    rgb.push(alpha);
+                    rgb.push(alpha);
+                }
+                BitmapBuffer::Rgb(rgb)
+            },
+        };
+
+        Ok(RasterizedGlyph {
+            character,
+            width: bounds.right - bounds.left,
+            height: bounds.bottom - bounds.top,
+            top: -bounds.top,
+            left: bounds.left,
+            advance,
+            side_bearings,
+            buffer,
+        })
+    }
+
+    /// Rasterize a batch of glyphs.
+    ///
+    /// This is equivalent to calling [`crate::Rasterize::get_glyph`] for every key, but the
+    /// rendering setup is only done once for the entire batch. The returned results are in the
+    /// same order as `keys`. Errors of the rendering setup are returned for the entire batch.
+    pub fn get_glyphs(
+        &mut self,
+        keys: &[GlyphKey],
+    ) -> Result<Vec<Result<RasterizedGlyph, Error>>, Error> {
+        let settings = self.rasterization_settings()?;
+        Ok(keys.iter().map(|glyph| self.get_glyph_with_settings(&settings, *glyph, None)).collect())
+    }
+
+    /// Rasterize a glyph with custom options.
+    ///
+    /// Options which aren't set fall back to the rasterizer's configuration.
+    pub fn get_glyph_with(
+        &mut self,
+        glyph: GlyphKey,
+        options: RasterizeOptions,
+    ) -> Result<RasterizedGlyph, Error> {
+        let mut settings = match options.rendering_mode {
+            Some(rendering_mode) => self.rasterization_settings_for(rendering_mode)?,
+            None => self.rasterization_settings()?,
+        };
+
+        if let Some(grid_fitting) = options.grid_fitting {
+            settings.set_grid_fitting(grid_fitting);
+        }
+
+        self.get_glyph_with_settings(&settings, glyph, None)
+    }
+
+    /// Rasterize a character followed by a Unicode variation selector.
+    ///
+    /// This allows picking a specific presentation of a character, like the emoji presentation
+    /// selected by `U+FE0F` or the text presentation selected by `U+FE0E`. If neither the font
+    /// nor the system fallback fonts have a glyph for the variation sequence, the character's
+    /// default glyph is used.
+    pub fn get_glyph_with_variation(
+        &mut self,
+        glyph: GlyphKey,
+        selector: char,
+    ) -> Result<RasterizedGlyph, Error> {
+        let settings = self.rasterization_settings()?;
+        self.get_glyph_with_settings(&settings, glyph, Some(selector))
+    }
+
+    fn get_glyph_with_settings(
+        &self,
+        settings: &RasterizationSettings,
+        glyph: GlyphKey,
+        selector: Option<char>,
+    ) -> Result<RasterizedGlyph, Error> {
+        match self.get_unfinished_glyph(settings, glyph, selector) {
+            Ok(glyph) => Ok(self.finish_glyph(settings, glyph)),
+            Err(Error::MissingGlyph(glyph)) => {
+                Err(Error::MissingGlyph(self.finish_glyph(settings, glyph)))
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Apply the alpha convention, padding and monochrome output to a rasterized coverage glyph.
+    fn finish_glyph(
+        &self,
+        settings: &RasterizationSettings,
+        mut glyph: RasterizedGlyph,
+    ) -> RasterizedGlyph {
+        if let (false, BitmapBuffer::Rgba(buffer)) = (self.premultiply_alpha, &mut glyph.buffer) {
+            color::unpremultiply(buffer);
+        }
+
+        let glyph = glyph.pad(self.glyph_padding);
+
+        if self.mono_output && self.is_aliased(settings) {
+            glyph.to_mono(self.mono_threshold)
+        } else {
+            glyph
+        }
+    }
+
+    /// Check if glyphs are rendered without antialiasing.
+    fn is_aliased(&self, settings: &RasterizationSettings) -> bool {
+        settings.rendering_mode == DWRITE_RENDERING_MODE1_ALIASED
+            || self.antialias_mode == AntialiasMode::None
+    }
+
+    fn get_unfinished_glyph(
+        &self,
+        settings: &RasterizationSettings,
+        glyph: GlyphKey,
+        selector: Option<char>,
+    ) -> Result<RasterizedGlyph, Error> {
+        em_size(glyph.size)?;
+        let (font, glyph_index) = self.resolve_glyph(glyph.font_key, glyph.character, selector)?;
+
+        let is_missing = self.is_missing_glyph(glyph_index);
+
+        if let (Some(scale), false) = (self.synthetic_smallcaps, is_missing) {
+            if let Some(rasterized_glyph) =
+                self.smallcaps_glyph(settings, &font, glyph, glyph_index, scale)?
+            {
+                return Ok(rasterized_glyph);
+            }
+        }
+
+        let glyph_index = if is_missing { self.notdef_index } else { glyph_index };
+        let rasterized_glyph =
+            self.rasterize_glyph(settings, &font, glyph.size, glyph.character, glyph_index)?;
+
+        if !is_missing {
+            return Ok(rasterized_glyph);
+        }
+
+        let RasterizedGlyph { character, advance, .. } = rasterized_glyph;
+        let rasterized_glyph = match self.missing_glyph_policy {
+            MissingGlyphPolicy::NotdefGlyph => {
+                self.replacement_glyph(settings, glyph)?.unwrap_or(rasterized_glyph)
+            },
+            MissingGlyphPolicy::Blank => RasterizedGlyph {
+                character,
+                advance,
+                buffer: BitmapBuffer::Rgb(Vec::new()),
+                ..Default::default()
+            },
+            MissingGlyphPolicy::HexBox => {
+                let font_metrics = font.face.metrics().metrics0();
+                let scale = glyph.size.as_px() / f32::from(font_metrics.designUnitsPerEm);
+                let height = (f32::from(font_metrics.ascent) * scale).round() as i32;
+                tofu::hex_box(character, advance.0, height, advance)
+            },
+        };
+
+        Err(Error::MissingGlyph(rasterized_glyph))
+    }
+
+    /// Check if a glyph index stands for a missing character.
+    ///
+    /// Besides the unmapped glyph `0`, the configured `.notdef` glyph is treated as missing too.
+    fn is_missing_glyph(&self, glyph_index: u16) -> bool {
+        glyph_index == MISSING_GLYPH_INDEX || glyph_index == self.notdef_index
+    }
+
+    /// Rasterize a lowercase letter as small capital.
+    ///
+    /// The font's own small capitals from its `smcp` feature are preferred. Otherwise the
+    /// uppercase glyph is rasterized at `scale` times the size, keeping the advance of the
+    /// lowercase glyph. Returns `None` for characters without a single uppercase letter.
+    fn smallcaps_glyph(
+        &self,
+        settings: &RasterizationSettings,
+        font: &Font,
+        glyph: GlyphKey,
+        glyph_index: u16,
+        scale: f32,
+    ) -> Result<Option<RasterizedGlyph>, Error> {
+        let mut uppercase = glyph.character.to_uppercase();
+        let uppercase = match (uppercase.next(), uppercase.next()) {
+            (Some(uppercase), None) if uppercase != glyph.character => uppercase,
+            _ => return Ok(None),
+        };
+
+        let mut buffer = [0; 4];
+        let text = glyph.character.encode_utf8(&mut buffer);
+        let em_size = em_size(glyph.size)?;
+        let features = [SMALL_CAPS_FEATURE];
+        let shaped = shaping::shape_with_features(text, &font.face, em_size, &features)?;
+        if let [shaped] = shaped.as_slice() {
+            if shaped.glyph_index != glyph_index && shaped.glyph_index != MISSING_GLYPH_INDEX {
+                let rasterized_glyph = self.rasterize_glyph(
+                    settings,
+                    font,
+                    glyph.size,
+                    glyph.character,
+                    shaped.glyph_index,
+                )?;
+                return Ok(Some(rasterized_glyph));
+            }
+        }
+
+        let uppercase_index = self.get_glyph_index(&font.face, uppercase);
+        if uppercase_index == MISSING_GLYPH_INDEX {
+            return Ok(None);
+        }
+
+        let size = glyph.size.scale(scale);
+        let mut rasterized_glyph =
+            self.rasterize_glyph(settings, font, size, glyph.character, uppercase_index)?;
+        let (advance, _) =
+            glyph_metrics(&font.face, glyph_index, em_size, self.vertical, self.advance_rounding)?;
+
+        // Keep the ink of the uppercase glyph within the advance of the lowercase glyph.
+        rasterized_glyph.side_bearings.1 += (advance.0 - rasterized_glyph.advance.0) as f32;
+        rasterized_glyph.advance = advance;
+
+        Ok(Some(rasterized_glyph))
+    }
+
+    /// Rasterize `U+FFFD` in place of a character which is missing from all fonts.
+    ///
+    /// Returns `None` if no font has a replacement character glyph.
+    fn replacement_glyph(
+        &self,
+        settings: &RasterizationSettings,
+        glyph: GlyphKey,
+    ) -> Result<Option<RasterizedGlyph>, Error> {
+        if !self.replacement_char_fallback {
+            return Ok(None);
+        }
+
+        let (font, glyph_index) =
+            self.resolve_glyph(glyph.font_key, char::REPLACEMENT_CHARACTER, None)?;
+        if self.is_missing_glyph(glyph_index) {
+            return Ok(None);
+        }
+
+        let rasterized_glyph =
+            self.rasterize_glyph(settings, &font, glyph.size, glyph.character, glyph_index)?;
+        Ok(Some(rasterized_glyph))
+    }
+
+    /// Rasterize a glyph as a signed distance field.
+    ///
+    /// The distance field is generated from the glyph's outline and stored as a
+    /// [`BitmapBuffer::Alpha`], with `128` on the outline. Coverage reaches `0` and `255` at
+    /// `spread` pixels outside and inside of the outline respectively. The bitmap is padded by
+    /// `spread` pixels on every side, which is already accounted for in the glyph's `top` and
+    /// `left` bearings.
+    pub fn get_glyph_sdf(
+        &mut self,
+        glyph: GlyphKey,
+        spread: f32,
+    ) -> Result<RasterizedGlyph, Error> {
+        let em_size = em_size(glyph.size)?;
+        let (font, glyph_index) = self.resolve_glyph(glyph.font_key, glyph.character, None)?;
+
+        let path = outline::glyph_path(&font.face, em_size, glyph_index)?;
+        let contours = outline::flatten(&path);
+        let mut rasterized_glyph =
+            outline::signed_distance_field(&contours, glyph.character, spread);
+        (rasterized_glyph.advance, rasterized_glyph.side_bearings) =
+            glyph_metrics(&font.face, glyph_index, em_size, self.vertical, self.advance_rounding)?;
+
+        if self.is_missing_glyph(glyph_index) {
+            Err(Error::MissingGlyph(rasterized_glyph))
+        } else {
+            Ok(rasterized_glyph)
+        }
+    }
+
+    /// Rasterize the outline of a glyph stroked with a line instead of filling it.
+    ///
+    /// The stroke is `stroke_width` pixels wide and centered on the outline. The result is a
+    /// [`BitmapBuffer::Alpha`] covering the entire stroke, with the stroke expansion included in
+    /// the glyph's `top` and `left` bearings and its side bearings. The glyph's character is
+    /// `'\0'`, since it's only identified by its glyph index. Fonts without outlines return
+    /// [`Error::Unsupported`].
+    pub fn get_glyph_stroked(
+        &mut self,
+        font_key: FontKey,
+        glyph_index: u16,
+        size: Size,
+        stroke_width: f32,
+    ) -> Result<RasterizedGlyph, Error> {
+        let font = self.get_loaded_font(font_key)?;
+        if !font.has_outlines {
+            return Err(Error::Unsupported("stroking glyphs without outlines"));
+        }
+
+        let face = &font.face;
+        let em_size = em_size(size)?;
+
+        let path = outline::glyph_path(face, em_size, glyph_index)?;
+        let mut rasterized_glyph =
+            outline::stroke(&outline::flatten(&path), char::default(), stroke_width);
+        let (advance, (left, right)) =
+            glyph_metrics(face, glyph_index, em_size, self.vertical, self.advance_rounding)?;
+        let expansion = stroke_width / 2.;
+        rasterized_glyph.advance = advance;
+        rasterized_glyph.side_bearings = (left - expansion, right - expansion);
+
+        // Strokes aren't coverage of the glyph, so they're never packed into monochrome bitmaps.
+        Ok(rasterized_glyph.pad(self.glyph_padding))
+    }
+
+    /// Rasterize a glyph at an integer size in pixels.
+    ///
+    /// The pixel size is used as the exact em size, so the same request always produces bitmaps
+    /// with the same dimensions.
+    pub fn get_glyph_px(
+        &mut self,
+        font_key: FontKey,
+        character: char,
+        px: u32,
+    ) -> Result<RasterizedGlyph, Error> {
+        let size = Size::from_px(px as f32);
+        crate::Rasterize::get_glyph(self, GlyphKey { character, font_key, size })
+    }
+
+    /// Get the metrics of a font at an integer size in pixels.
+    ///
+    /// The pixel size is used as the exact em size, like [`Self::get_glyph_px`].
+    pub fn metrics_px(&self, font_key: FontKey, px: u32) -> Result<Metrics, Error> {
+        crate::Rasterize::metrics(self, font_key, Size::from_px(px as f32))
+    }
+
+    /// Check if a loaded font has glyph outlines.
+    ///
+    /// Fonts without outlines only provide embedded bitmaps, so [`Self::glyph_outline`] returns
+    /// empty paths for all of their glyphs.
+    pub fn has_outlines(&self, font_key: FontKey) -> Result<bool, Error> {
+        Ok(self.get_loaded_font(font_key)?.has_outlines)
+    }
+
+    /// Get the outline of a glyph.
+    ///
+    /// The outline is scaled to `size`, see [`PathCommand`] for the coordinate system. Glyphs
+    /// without outline, like whitespace, return an empty path.
+    pub fn glyph_outline(
+        &self,
+        font_key: FontKey,
+        glyph_index: u16,
+        size: Size,
+    ) -> Result<Vec<PathCommand>, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        outline::glyph_path(face, em_size(size)?, glyph_index)
+    }
+
+    /// Find the font and glyph index used to render a character.
+    ///
+    /// If the character is missing from the requested font, the system fallback fonts are used
+    /// instead. The glyph index is `MISSING_GLYPH_INDEX` if no font has the character.
+    ///
+    /// With a variation `selector`, the glyph for the variation sequence is preferred. Since
+    /// fonts rarely provide both presentations, the emoji presentation is also looked up in the
+    /// fallback fonts when the requested font has no glyph for the sequence.
+    fn resolve_glyph(
+        &self,
+        font_key: FontKey,
+        character: char,
+        selector: Option<char>,
+    ) -> Result<(Cow<'_, Font>, u16), Error> {
+        let loaded_font = self.get_loaded_font(font_key)?;
+
+        let variation_index = |face: &FontFace| {
+            let selector = selector?;
+            self.get_variation_glyph_index(face, character, selector)
+        };
+
+        if let Some(glyph_index) = variation_index(&loaded_font.face) {
+            return Ok((Cow::Borrowed(loaded_font), glyph_index));
+        }
+
+        let glyph_index = self.get_glyph_index(&loaded_font.face, character);
+        if glyph_index == MISSING_GLYPH_INDEX || selector == Some(EMOJI_PRESENTATION_SELECTOR) {
+            let fallback_font =
+                self.cached_fallback_font(font_key, loaded_font, character, selector);
+            if let Some(fallback_font) = fallback_font {
+                let glyph_index = variation_index(&fallback_font.face)
+                    .unwrap_or_else(|| self.get_glyph_index(&fallback_font.face, character));
+
+                // Only use the fallback if it actually covers the complete codepoint.
+                if glyph_index != MISSING_GLYPH_INDEX {
+                    return Ok((Cow::Owned(fallback_font), glyph_index));
+                }
+            }
+        }
+
+        if let (MISSING_GLYPH_INDEX, Some(last_resort_font)) = (glyph_index, &self.last_resort_font)
+        {
+            let glyph_index = self.get_glyph_index(&last_resort_font.face, character);
+            if glyph_index != MISSING_GLYPH_INDEX {
+                return Ok((Cow::Owned(last_resort_font.clone()), glyph_index));
+            }
+        }
+
+        Ok((Cow::Borrowed(loaded_font), glyph_index))
+    }
+
+    /// Find the font which renders the first character of a text.
+    ///
+    /// This runs the same system font fallback as [`crate::Rasterize::get_glyph`], without
+    /// rasterizing anything. A variation selector following the first character is taken into
+    /// account, like with [`Self::get_glyph_with_variation`]. If no font has the character or the
+    /// text is empty, the requested font is returned.
+    pub fn resolve_font_for(
+        &self,
+        font_key: FontKey,
+        text: &str,
+    ) -> Result<ResolvedFontInfo, Error> {
+        let mut characters = text.chars();
+        let character = characters.next().unwrap_or_default();
+        let selector = characters.next().filter(|next| ('\u{FE00}'..='\u{FE0F}').contains(next));
+
+        let (font, glyph_index) = self.resolve_glyph(font_key, character, selector)?;
+
+        Ok(ResolvedFontInfo {
+            family_name: font.family_name.clone(),
+            style: ResolvedStyle::new(font.weight, font.style, font.stretch),
+            is_fallback: matches!(font, Cow::Owned(_)),
+            has_glyph: glyph_index != MISSING_GLYPH_INDEX,
+        })
+    }
+
+    /// Rescan the system font collection for newly installed fonts.
+    ///
+    /// This also picks up changes to the user's locale, which affects fallback font selection.
+    /// Only subsequent lookups of new font descriptions are affected. Already loaded fonts keep
+    /// their [`FontKey`] and loading the same [`FontDesc`] again still returns the font which was
+    /// resolved before the refresh.
+    pub fn refresh_system_fonts(&mut self) {
+        self.available_fonts = FontCollection::get_system(true);
+        self.fallback_sequence = FontFallback::get_system_fallback();
+        self.fallback_cache.get_mut().fonts.clear();
+        self.locale = get_current_locale();
+    }
+
+    /// Unload all fonts and clear all caches.
+    ///
+    /// This is cheaper than creating a new rasterizer, since the system font collection and
+    /// fallback are kept, as is the rendering configuration. All previously returned
+    /// [`FontKey`]s become invalid and return [`Error::UnknownFontKey`].
+    pub fn clear(&mut self) {
+        self.fonts.clear();
+        self.keys.clear();
+        self.metrics_cache.get_mut().clear();
+        self.fallback_cache.get_mut().fonts.clear();
+    }
+
+    /// Get the glyph of a character in a loaded font.
+    ///
+    /// Unlike [`crate::Rasterize::get_glyph`], no fallback fonts are consulted. Returns `None` if
+    /// the font's character map has no glyph for the character.
+    pub fn glyph_for_char(&self, font_key: FontKey, character: char) -> Result<Option<u16>, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        let glyph_index = self.get_glyph_index(face, character);
+        Ok(Some(glyph_index).filter(|&glyph_index| glyph_index != MISSING_GLYPH_INDEX))
+    }
+
+    /// Shape a string and rasterize all resulting glyphs.
+    ///
+    /// This applies the font's shaping rules, like ligatures, Arabic joining and Indic
+    /// reordering. Glyphs are returned in logical order and no font fallback is performed.
+    pub fn shape_and_rasterize(
+        &mut self,
+        text: &str,
+        font_key: FontKey,
+        size: Size,
+    ) -> Result<Vec<ShapedGlyph>, Error> {
+        let settings = self.rasterization_settings()?;
+        let font = self.get_loaded_font(font_key)?;
+
+        shaping::shape(text, &font.face, em_size(size)?)?
+            .into_iter()
+            .map(|positioned| {
+                let character = text[positioned.cluster..].chars().next().unwrap_or_default();
+                let glyph =
+                    self.rasterize_glyph(&settings, font, size, character, positioned.glyph_index)?;
+                let glyph = self.finish_glyph(&settings, glyph);
+
+                Ok(ShapedGlyph {
+                    glyph_index: positioned.glyph_index,
+                    cluster: positioned.cluster,
+                    advance: self.advance_rounding.apply(positioned.advance),
+                    offset: positioned.offset,
+                    glyph,
+                })
+            })
+            .collect()
+    }
+
+    /// Check if a glyph is rendered in color.
+    ///
+    /// Glyphs with layers in the font's `COLR` table or bitmaps in its `CBDT` table are
+    /// rasterized to [`BitmapBuffer::Rgba`] buffers, all other glyphs to [`BitmapBuffer::Rgb`].
+    pub fn is_color_glyph(&self, font_key: FontKey, glyph_index: u16) -> Result<bool, Error> {
+        let font = self.get_loaded_font(font_key)?;
+
+        if bitmap::has_color_bitmap(font, glyph_index)? {
+            return Ok(true);
+        }
+
+        let settings = self.rasterization_settings()?;
+        color::has_color_layers(&settings, &font.face, glyph_index)
+    }
+
+    /// Rasterize the `.notdef` glyph of a font.
+    ///
+    /// Unlike missing glyphs, this always returns the font's own `.notdef` glyph, regardless of
+    /// the [`MissingGlyphPolicy`]. The glyph is the one configured with
+    /// [`Self::set_notdef_index`]. The glyph's character is `'\0'`.
+    pub fn get_notdef_glyph(
+        &mut self,
+        font_key: FontKey,
+        size: Size,
+    ) -> Result<RasterizedGlyph, Error> {
+        let settings = self.rasterization_settings()?;
+        let font = self.get_loaded_font(font_key)?;
+
+        let glyph =
+            self.rasterize_glyph(&settings, font, size, char::default(), self.notdef_index)?;
+        Ok(self.finish_glyph(&settings, glyph))
+    }
+
+    /// Rasterize a grapheme cluster as a single glyph.
+    ///
+    /// The cluster is shaped as a whole, so emoji sequences like ZWJ sequences, flags and skin
+    /// tone modifiers resolve to their combined glyph. The cluster is shaped with a system
+    /// fallback font if the requested font can't display it. If no font has a combined glyph,
+    /// the individual glyphs are composited next to each other.
+    pub fn get_cluster(
+        &mut self,
+        cluster: &str,
+        font_key: FontKey,
+        size: Size,
+    ) -> Result<RasterizedGlyph, Error> {
+        let settings = self.rasterization_settings()?;
+        let loaded_font = self.get_loaded_font(font_key)?;
+        let character = cluster.chars().next().unwrap_or_default();
+        let em_size = em_size(size)?;
+
+        let mut font = Cow::Borrowed(loaded_font);
+        let mut glyphs = shaping::shape(cluster, &font.face, em_size)?;
+
+        if glyphs.iter().any(|glyph| glyph.glyph_index == MISSING_GLYPH_INDEX) {
+            let utf16: Vec<u16> = cluster.encode_utf16().collect();
+            let fallback_font = self
+                .map_fallback_font(loaded_font, &utf16, character.len_utf16())
+                .and_then(|fallback_font| Font::try_from(fallback_font).ok());
+            if let Some(fallback_font) = fallback_font {
+                glyphs = shaping::shape(cluster, &fallback_font.face, em_size)?;
+                font = Cow::Owned(fallback_font);
+            }
+        }
+
+        let mut pen = 0.;
+        let mut positioned = Vec::with_capacity(glyphs.len());
+        for glyph in glyphs {
+            let rasterized_glyph =
+                self.rasterize_glyph(&settings, &font, size, character, glyph.glyph_index)?;
+            let position = ((pen + glyph.offset.0).round() as i32, glyph.offset.1.round() as i32);
+            positioned.push((rasterized_glyph, position));
+            pen += glyph.advance;
+        }
+
+        let advance = (self.advance_rounding.apply(pen) as i32, 0);
+        Ok(self.finish_glyph(&settings, cluster::composite(character, &positioned, advance)))
+    }
+
+    /// Get the ink extents of a glyph without rasterizing it.
+    pub fn glyph_bounds(
+        &self,
+        font_key: FontKey,
+        glyph_index: u16,
+        size: Size,
+    ) -> Result<GlyphBounds, Error> {
+        em_size(size)?;
+        let face = &self.get_loaded_font(font_key)?.face;
+        let scale = design_unit_scale(face.metrics().metrics0().designUnitsPerEm, size);
+
+        let glyph_metrics = face.design_glyph_metrics(&[glyph_index], false)?;
+        let glyph_metrics = glyph_metrics.first().ok_or(Error::MetricsNotFound)?;
+
+        let advance_width = i64::from(glyph_metrics.advanceWidth);
+        let advance_height = i64::from(glyph_metrics.advanceHeight);
+        let vertical_origin = i64::from(glyph_metrics.verticalOriginY);
+        let to_pixels = |design_units: i64| (design_units as f64 * scale) as f32;
+
+        Ok(GlyphBounds {
+            left: to_pixels(i64::from(glyph_metrics.leftSideBearing)),
+            top: to_pixels(vertical_origin - i64::from(glyph_metrics.topSideBearing)),
+            right: to_pixels(advance_width - i64::from(glyph_metrics.rightSideBearing)),
+            bottom: to_pixels(
+                vertical_origin - advance_height + i64::from(glyph_metrics.bottomSideBearing),
+            ),
+            advance: self.advance_rounding.apply(to_pixels(advance_width)),
+        })
+    }
+
+    /// Get the left and right side bearings of a glyph in pixels.
+    ///
+    /// Negative bearings indicate that the glyph's ink overhangs its advance box, like the hook
+    /// of an italic `f`.
+    pub fn glyph_side_bearings(
+        &self,
+        font_key: FontKey,
+        glyph_index: u16,
+        size: Size,
+    ) -> Result<(f32, f32), Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        let (_, side_bearings) =
+            glyph_metrics(face, glyph_index, em_size(size)?, false, self.advance_rounding)?;
+        Ok(side_bearings)
+    }
+
+    /// Get the path of the file backing a loaded font.
+    ///
+    /// Returns `None` for fonts which were not loaded from the local file system.
+    pub fn font_path(&self, font_key: FontKey) -> Result<Option<PathBuf>, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        let files = face.files()?;
+
+        Ok(files.first().and_then(|file| file.font_file_path().ok()))
+    }
+
+    /// Compute the metrics of a font, bypassing the metrics cache.
+    fn compute_metrics(&self, key: FontKey, size: Size) -> Result<Metrics, Error> {
+        em_size(size)?;
+        let face = &self.get_loaded_font(key)?.face;
+        let vmetrics = face.metrics().metrics0();
+
+        let scale = design_unit_scale(vmetrics.designUnitsPerEm, size);
+
+        let underline_position = f64::from(vmetrics.underlinePosition) * scale;
+        let underline_thickness = f64::from(vmetrics.underlineThickness) * scale;
+
+        let strikeout_position = f64::from(vmetrics.strikethroughPosition) * scale;
+        let strikeout_thickness = f64::from(vmetrics.strikethroughThickness) * scale;
+
+        let (ascent, descent, line_gap) = match self.line_metrics(face)? {
+            Some(line_metrics) => (
+                f64::from(line_metrics.ascent) * scale,
+                f64::from(line_metrics.descent) * scale,
+                f64::from(line_metrics.line_gap) * scale,
+            ),
+            None => (
+                f64::from(vmetrics.ascent) * scale,
+                -f64::from(vmetrics.descent) * scale,
+                f64::from(vmetrics.lineGap) * scale,
+            ),
+        };
+
+        let line_height = ascent - descent + line_gap;
+
+        let (overline_position, overline_thickness) =
+            Metrics::overline(ascent as f32, underline_thickness as f32);
+
+        let double_underline_position = underline_position - 2. * underline_thickness;
+
+        // Since all monospace characters have the same width, we use `!` for horizontal metrics.
+        let character = '!';
+        let glyph_index = self.get_glyph_index(face, character);
+
+        let glyph_metrics =
+            face.design_glyph_metrics(&[glyph_index], false).map_err(|_| Error::MetricsNotFound)?;
+        let hmetrics = glyph_metrics.first().ok_or(Error::MetricsNotFound)?;
+
+        let mut average_advance = f64::from(hmetrics.advanceWidth) * scale;
+
+        debug!(
+            "crossfont metrics: designUnitsPerEm={}, size_px={}, scale={}, advanceWidth={}, \
+             average_advance={}, ascent={}, descent={}, line_gap={}, line_height={}",
+            vmetrics.designUnitsPerEm,
+            size.as_px(),
+            scale,
+            hmetrics.advanceWidth,
+            average_advance,
+            ascent,
+            descent,
+            line_gap,
+            line_height,
+        );
+
+        // Apply user adjustments on top of the font's metrics.
+        let mut line_height = line_height * f64::from(self.line_height_scale);
+        if let Some(metrics_override) = self.metrics_override {
+            average_advance = metrics_override.average_advance.unwrap_or(average_advance);
+            line_height = metrics_override.line_height.unwrap_or(line_height);
+        }
+
+        Ok(Metrics {
+            ascent: ascent as f32,
+            descent: descent as f32,
+            line_gap: line_gap as f32,
+            average_advance,
+            line_height,
+            underline_position: underline_position as f32,
+            underline_thickness: underline_thickness as f32,
+            strikeout_position: strikeout_position as f32,
+            strikeout_thickness: strikeout_thickness as f32,
+            overline_position,
+            overline_thickness,
+            double_underline_position: double_underline_position as f32,
+        })
+    }
+
+    /// Read the line metrics from the table selected by the [`LineMetricsSource`].
+    ///
+    /// Returns `None` if the font doesn't have the table.
+    fn line_metrics(&self, face: &FontFace) -> Result<Option<tables::LineMetrics>, Error> {
+        let os2 = face.font_table(tables::OS2)?;
+        let use_typo_metrics = match self.line_metrics_source {
+            LineMetricsSource::Hhea => false,
+            LineMetricsSource::TypoOs2 => true,
+            LineMetricsSource::Auto => os2.as_deref().is_some_and(tables::use_typo_metrics),
+        };
+
+        let line_metrics = if use_typo_metrics {
+            os2.and_then(|os2| tables::typo_line_metrics(&os2))
+        } else {
+            face.font_table(tables::HHEA)?.and_then(|hhea| tables::horizontal_line_metrics(&hhea))
+        };
+
+        Ok(line_metrics)
+    }
+
+    /// Get the number of design units per em of a loaded font.
+    pub fn units_per_em(&self, font_key: FontKey) -> Result<u16, Error> {
+        Ok(self.get_loaded_font(font_key)?.face.metrics().metrics0().designUnitsPerEm)
+    }
+
+    /// Get the factor converting a font's design units to pixels at the given size.
+    ///
+    /// This is the same scale used for all metrics reported by the rasterizer.
+    pub fn scale_for(&self, font_key: FontKey, size: Size) -> Result<f64, Error> {
+        em_size(size)?;
+        Ok(design_unit_scale(self.units_per_em(font_key)?, size))
+    }
+
+    /// Get the unscaled metrics of a glyph in design units.
+    pub fn raw_glyph_metrics(
+        &self,
+        font_key: FontKey,
+        glyph_index: u16,
+    ) -> Result<RawGlyphMetrics, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+
+        let glyph_metrics = face.design_glyph_metrics(&[glyph_index], false)?;
+        let glyph_metrics = glyph_metrics.first().ok_or(Error::MetricsNotFound)?;
+
+        Ok(RawGlyphMetrics {
+            advance_width: glyph_metrics.advanceWidth,
+            advance_height: glyph_metrics.advanceHeight,
+            left_side_bearing: glyph_metrics.leftSideBearing,
+            right_side_bearing: glyph_metrics.rightSideBearing,
+            top_side_bearing: glyph_metrics.topSideBearing,
+            bottom_side_bearing: glyph_metrics.bottomSideBearing,
+            vertical_origin_y: glyph_metrics.verticalOriginY,
+        })
+    }
+
+    /// Get the horizontal advances of multiple characters in pixels.
+    ///
+    /// The advances are rounded like those of rasterized glyphs, see
+    /// [`Self::set_advance_rounding`]. Characters missing from the font use the advance of its
+    /// `.notdef` glyph, no fallback fonts are consulted.
+    pub fn advances(
+        &self,
+        font_key: FontKey,
+        characters: &[char],
+        size: Size,
+    ) -> Result<Vec<f32>, Error> {
+        em_size(size)?;
+        let face = &self.get_loaded_font(font_key)?.face;
+
+        let code_points: Vec<u32> = characters.iter().map(|&character| character as u32).collect();
+        let mut glyph_indices = face.glyph_indices(&code_points)?;
+
+        // Resolve characters outside of the BMP which DirectWrite's `cmap` lookup missed.
+        for (glyph_index, &character) in glyph_indices.iter_mut().zip(characters) {
+            if *glyph_index == MISSING_GLYPH_INDEX && u32::from(character) > 0xFFFF {
+                *glyph_index = self.get_glyph_index(face, character);
+            }
+        }
+
+        let scale = design_unit_scale(face.metrics().metrics0().designUnitsPerEm, size);
+
+        let glyph_metrics = face.design_glyph_metrics(&glyph_indices, false)?;
+        let advances = glyph_metrics
+            .iter()
+            .map(|metrics| scale_advance(metrics.advanceWidth, scale, self.advance_rounding))
+            .collect();
+
+        Ok(advances)
+    }
+
+    /// Get the unscaled kerning adjustment between two glyphs in design units.
+    ///
+    /// Only the font's legacy `kern` table is consulted.
+    pub fn raw_kerning(&self, font_key: FontKey, left: u16, right: u16) -> Result<i32, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        Ok(face.glyph_pair_kerning_adjustment(left, right)?)
+    }
+
+    /// Register a DirectWrite font, bypassing the name based font matching.
+    ///
+    /// Every call assigns a new key, even when the same font is registered multiple times.
+    pub fn register_font_face(&mut self, font: dwrote::Font) -> Result<FontKey, Error> {
+        let font = Font::try_from(font)?;
+
+        let key = FontKey::next();
+        self.fonts.insert(key, font);
+
+        Ok(key)
+    }
+
+    /// Get the key of a font description which was already loaded.
+    ///
+    /// Returns `None` if the description wasn't passed to [`crate::Rasterize::load_font`] yet.
+    pub fn font_key_for(&self, desc: &FontDesc) -> Option<FontKey> {
+        self.keys.get(desc).copied()
+    }
+
+    /// Iterate over the keys of all loaded fonts, in no particular order.
+    pub fn loaded_keys(&self) -> impl Iterator<Item = FontKey> + '_ {
+        self.fonts.keys().copied()
+    }
+
+    /// Iterate over all loaded fonts with their family names, in no particular order.
+    pub fn loaded_fonts(&self) -> impl Iterator<Item = (FontKey, &str)> + '_ {
+        self.fonts.iter().map(|(key, font)| (*key, font.family_name.as_str()))
+    }
+
+    /// Get the actual style of a loaded font.
+    ///
+    /// Fonts are matched to the closest available style, so this can differ from the requested
+    /// style, like a medium face being loaded when a family has no bold face.
+    pub fn resolved_style(&self, font_key: FontKey) -> Result<ResolvedStyle, Error> {
+        let font = self.get_loaded_font(font_key)?;
+        Ok(ResolvedStyle::new(font.weight, font.style, font.stretch))
+    }
+
+    /// List all faces of a font family.
+    ///
+    /// This includes faces which DirectWrite simulates, like synthetic bold or oblique faces.
+    pub fn matching_faces(&self, family: &str) -> Result<Vec<FaceInfo>, Error> {
+        let family =
+            self.find_family(family).ok_or_else(|| Error::FontNameNotFound(family.into()))?;
+
+        let count = family.get_font_count();
+        let faces = (0..count)
+            .filter_map(|idx| family.font(idx).ok())
+            .map(|font| FaceInfo {
+                name: font.face_name(),
+                style: ResolvedStyle::new(font.weight(), font.style(), font.stretch()),
+            })
+            .collect();
+
+        Ok(faces)
+    }
+
+    /// Get the number of glyphs in a loaded font.
+    pub fn glyph_count(&self, font_key: FontKey) -> Result<u16, Error> {
+        Ok(self.get_loaded_font(font_key)?.face.get_glyph_count())
+    }
+
+    /// Get the PostScript name of a loaded font.
+    ///
+    /// Returns `None` if the font has no PostScript name.
+    pub fn postscript_name(&self, font_key: FontKey) -> Result<Option<String>, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        let names = face.font_table(tables::NAME)?;
+
+        Ok(names.and_then(|names| tables::name_string(&names, tables::POSTSCRIPT_NAME_ID)))
+    }
+
+    /// Get the copyright, license and other descriptive strings of a loaded font.
+    ///
+    /// Strings missing from the font's `name` table are `None`.
+    pub fn font_info(&self, font_key: FontKey) -> Result<FontInfo, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        let names = match face.font_table(tables::NAME)? {
+            Some(names) => names,
+            None => return Ok(FontInfo::default()),
+        };
+
+        let name = |name_id| tables::name_string(&names, name_id);
+        Ok(FontInfo {
+            copyright: name(tables::COPYRIGHT_NAME_ID),
+            family_name: name(tables::FAMILY_NAME_ID),
+            full_name: name(tables::FULL_NAME_ID),
+            version: name(tables::VERSION_NAME_ID),
+            designer: name(tables::DESIGNER_NAME_ID),
+            license: name(tables::LICENSE_NAME_ID),
+            license_url: name(tables::LICENSE_URL_NAME_ID),
+        })
+    }
+
+    /// Get the named instances of a loaded variable font.
+    ///
+    /// Instances are listed in the order of the font's `fvar` table, fonts without variations
+    /// have no named instances. Instances without a name in the `name` table are skipped.
+    pub fn named_instances(&self, font_key: FontKey) -> Result<Vec<NamedInstance>, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+        if !face.has_variations() {
+            return Ok(Vec::new());
+        }
+
+        let (fvar, names) = match (face.font_table(tables::FVAR)?, face.font_table(tables::NAME)?) {
+            (Some(fvar), Some(names)) => (fvar, names),
+            _ => return Ok(Vec::new()),
+        };
+
+        let instances = tables::fvar_instances(&fvar)
+            .into_iter()
+            .filter_map(|instance| {
+                let name = tables::name_string(&names, instance.name_id)?;
+                Some(NamedInstance { name, coordinates: instance.coordinates })
+            })
+            .collect();
+
+        Ok(instances)
+    }
+
+    /// Get the OpenType script tags supported by a loaded font, like `latn` or `arab`.
+    ///
+    /// The scripts are read from the script lists of the `GSUB` and `GPOS` tables, fonts without
+    /// these tables return no scripts.
+    pub fn supported_scripts(&self, font_key: FontKey) -> Result<Vec<[u8; 4]>, Error> {
+        let face = &self.get_loaded_font(font_key)?.face;
+
+        let mut scripts = Vec::new();
+        for table in [tables::GSUB, tables::GPOS] {
+            let tags = face.font_table(table)?.map(|layout| tables::script_tags(&layout));
+            for tag in tags.unwrap_or_default() {
+                if !scripts.contains(&tag) {
+                    scripts.push(tag);
+                }
+            }
+        }
+
+        Ok(scripts)
+    }
+
+    /// Get the metrics for vertical text layout.
+    ///
+    /// Fonts without vertical metrics are treated as if the vertical baseline runs through the
+    /// center of the em square.
+    pub fn vertical_metrics(&self, key: FontKey, size: Size) -> Result<VerticalMetrics, Error> {
+        em_size(size)?;
+        let face = &self.get_loaded_font(key)?.face;
+
+        let design_units_per_em = face.metrics().metrics0().designUnitsPerEm;
+        let scale = design_unit_scale(design_units_per_em, size);
+        let to_pixels = |design_units: i16| (f64::from(design_units) * scale) as f32;
+
+        let header = face.font_table(tables::VHEA)?.and_then(|vhea| tables::vertical_header(&vhea));
+        let vertical_metrics = match header {
+            Some(header) => VerticalMetrics {
+                ascent: to_pixels(header.ascent),
+                descent: to_pixels(header.descent),
+                line_gap: to_pixels(header.line_gap),
+            },
+            None => {
+                let half_em = (f64::from(design_units_per_em) * scale / 2.) as f32;
+                VerticalMetrics { ascent: half_em, descent: -half_em, line_gap: 0. }
+            },
+        };
+
+        Ok(vertical_metrics)
+    }
+
+    /// Get the distance from the top of a line to the baseline which vertically centers capital
+    /// letters in the line.
+    ///
+    /// The line height is the one of [`crate::Rasterize::metrics`]. Fonts without a cap height
+    /// use their ascent instead.
+    pub fn cap_center_offset(&self, key: FontKey, size: Size) -> Result<f32, Error> {
+        let metrics = crate::Rasterize::metrics(self, key, size)?;
+
+        let font_metrics = self.get_loaded_font(key)?.face.metrics().metrics0();
+        let cap_height = match font_metrics.capHeight {
+            0 => metrics.ascent,
+            cap_height => {
+                let scale = design_unit_scale(font_metrics.designUnitsPerEm, size);
+                (f64::from(cap_height) * scale) as f32
+            },
+        };
+
+        Ok((metrics.line_height as f32 + cap_height) / 2.)
+    }
+
+    /// Darken glyph stems at small sizes.
+    ///
+    /// This boosts the coverage of glyphs rasterized from outlines, which keeps thin fonts
+    /// legible at small sizes. The effect is strongest for tiny glyphs and fades out for larger
+    /// ones, glyphs above 24 pixels are not affected at all. An `amount` of `1.0` is a good
+    /// starting point.
+    pub fn set_stem_darkening(&mut self, enabled: bool, amount: f32) {
+        self.stem_darkening = enabled.then_some(amount);
+    }
+
+    /// Get the stem darkening amount, `None` if stem darkening is disabled.
+    pub fn stem_darkening(&self) -> Option<f32> {
+        self.stem_darkening
+    }
+
+    /// Render lowercase letters as small capitals.
+    ///
+    /// Fonts with an OpenType `smcp` feature use their own small capitals. For all other fonts,
+    /// the uppercase letters are shrunk to `scale` times the font size, while keeping the advance
+    /// of the lowercase letters. A `scale` of `0.8` is a good starting point.
+    pub fn set_synthetic_smallcaps(&mut self, enabled: bool, scale: f32) {
+        self.synthetic_smallcaps = enabled.then_some(scale);
+    }
+
+    /// Get the scale of synthetic small capitals, `None` if small capitals are disabled.
+    pub fn synthetic_smallcaps(&self) -> Option<f32> {
+        self.synthetic_smallcaps
+    }
+
+    /// Set the coverage adjustments of a rendering mode.
+    ///
+    /// Every rendering mode keeps its own parameters, so they persist when switching between
+    /// modes. Aliased rendering can't be adjusted and automatic rendering uses the parameters of
+    /// the mode it resolves to.
+    pub fn set_rendering_params(&mut self, mode: super::RenderingMode, params: RenderingParams) {
+        match mode {
+            super::RenderingMode::Grayscale => self.grayscale_params = params,
+            super::RenderingMode::Subpixel => self.cleartype_params = params,
+            super::RenderingMode::Aliased | super::RenderingMode::Auto => (),
+        }
+    }
+
+    /// Get the coverage adjustments of a rendering mode.
+    ///
+    /// Automatic rendering returns the parameters of the mode it currently resolves to.
+    pub fn rendering_params(&self, mode: super::RenderingMode) -> RenderingParams {
+        match mode {
+            super::RenderingMode::Grayscale => self.grayscale_params,
+            super::RenderingMode::Subpixel => self.cleartype_params,
+            super::RenderingMode::Aliased => RenderingParams::ALIASED,
+            super::RenderingMode::Auto => match self.effective_rendering_mode() {
+                super::RenderingMode::Subpixel => self.cleartype_params,
+                _ => self.grayscale_params,
+            },
+        }
+    }
+
+    /// Enable or disable linear coverage output.
+    ///
+    /// DirectWrite's coverage is gamma-encoded, which is correct for blending in sRGB space. When
+    /// enabled, every coverage value `c` of grayscale and subpixel glyphs is converted to
+    /// `(c / 255) ^ gamma * 255`, using the [`RenderingParams::gamma`] of the active rendering
+    /// mode, which is `1.8` by default. This is required for blending in linear space. Color
+    /// glyphs and embedded bitmaps are not affected. Disabled by default.
+    pub fn set_linear_coverage(&mut self, enabled: bool) {
+        self.linear_coverage = enabled;
+    }
+
+    /// Check if linear coverage output is enabled.
+    pub fn linear_coverage(&self) -> bool {
+        self.linear_coverage
+    }
+
+    /// Use the configuration of a DirectWrite rendering params object.
+    ///
+    /// The object's gamma and enhanced contrast replace the [`RenderingParams`] of the grayscale
+    /// and subpixel rendering modes. Its ClearType level and pixel geometry are applied to
+    /// subpixel glyphs. Its rendering mode replaces the one picked for the grayscale and subpixel
+    /// rendering modes, unless it is `DWRITE_RENDERING_MODE_DEFAULT` or
+    /// `DWRITE_RENDERING_MODE_OUTLINE`.
+    ///
+    /// Passing `None` restores the defaults.
+    pub fn set_native_rendering_params(&mut self, params: Option<&dwrote::RenderingParams>) {
+        let native = match params {
+            Some(params) => unsafe { &*params.as_ptr() },
+            None => {
+                self.grayscale_params = RenderingParams::GRAYSCALE;
+                self.cleartype_params = RenderingParams::CLEARTYPE;
+                self.display_params = DisplayParams::default();
+                return;
+            },
+        };
+
+        let gamma = unsafe { native.GetGamma() };
+        let enhanced_contrast = unsafe { native.GetEnhancedContrast() };
+
+        // Grayscale contrast can only be configured separately since DirectWrite 1.1.
+        let grayscale_contrast = unsafe {
+            let mut params1: *mut IDWriteRenderingParams1 = ptr::null_mut();
+            let hr = native.QueryInterface(
+                &IDWriteRenderingParams1::uuidof(),
+                &mut params1 as *mut *mut IDWriteRenderingParams1 as *mut *mut c_void,
+            );
+            if hr == S_OK && !params1.is_null() {
+                ComPtr::from_raw(params1).GetGrayscaleEnhancedContrast()
+            } else {
+                enhanced_contrast
+            }
+        };
+
+        self.grayscale_params = RenderingParams { gamma, enhanced_contrast: grayscale_contrast };
+        self.cleartype_params = RenderingParams { gamma, enhanced_contrast };
+
+        let rendering_mode = match unsafe { native.GetRenderingMode() } {
+            dwrite::DWRITE_RENDERING_MODE_DEFAULT | dwrite::DWRITE_RENDERING_MODE_OUTLINE => None,
+            // The values of both enums are identical for all DirectWrite 1 rendering modes.
+            mode => Some(mode as DWRITE_RENDERING_MODE1),
+        };
+
+        self.display_params = DisplayParams {
+            cleartype_level: unsafe { native.GetClearTypeLevel() },
+            pixel_geometry: unsafe { native.GetPixelGeometry() },
+            rendering_mode,
+        };
+    }
+
+    /// Get the rendering mode requested with [`crate::Rasterize::set_rendering_mode`].
+    ///
+    /// This is the requested mode, not the one used after resolving [`RenderingMode::Auto`].
+    ///
+    /// [`RenderingMode::Auto`]: crate::RenderingMode::Auto
+    pub fn rendering_mode(&self) -> super::RenderingMode {
+        self.rendering_mode
+    }
+
+    /// Check if grid fitting is enabled.
+    pub fn grid_fitting(&self) -> bool {
+        self.grid_fitting
+    }
+
+    /// Check if the background was reported as opaque, `None` if it was never reported.
+    pub fn background_is_opaque(&self) -> Option<bool> {
+        self.background_opaque
+    }
+
+    /// Only grid fit glyphs up to a size in pixels.
+    ///
+    /// With a threshold, glyphs up to `threshold` pixels are grid fitted and larger glyphs are
+    /// not, regardless of [`crate::Rasterize::set_grid_fitting`]. With `None`, grid fitting is
+    /// controlled by the global toggle again. An explicit [`RasterizeOptions::grid_fitting`]
+    /// takes precedence over the threshold.
+    pub fn set_grid_fit_threshold(&mut self, threshold: Option<f32>) {
+        self.grid_fit_threshold = threshold;
+    }
+
+    /// Get the largest size in pixels which is grid fitted, `None` if there is no threshold.
+    pub fn grid_fit_threshold(&self) -> Option<f32> {
+        self.grid_fit_threshold
+    }
+
+    /// Select the palette used for rendering color glyphs.
+    ///
+    /// Fonts which don't have a palette with this index use their default palette instead.
+    pub fn set_color_palette(&mut self, index: u32) {
+        self.color_palette = index;
+    }
+
+    /// Get the palette index used for rendering color glyphs.
+    pub fn color_palette(&self) -> u32 {
+        self.color_palette
+    }
+
+    /// Get the palette index used for the color glyphs of a font.
+    fn color_palette_index(&self, face: &FontFace) -> u32 {
+        if self.color_palette == 0 {
+            return 0;
+        }
+
+        let palette_count = face
+            .font_table(tables::CPAL)
+            .ok()
+            .flatten()
+            .and_then(|cpal| tables::palette_count(&cpal))
+            .unwrap_or(0);
+
+        if self.color_palette < u32::from(palette_count) {
+            self.color_palette
+        } else {
+            0
+        }
+    }
+
+    /// Report vertical advances for rasterized glyphs.
+    ///
+    /// When enabled, the second component of [`RasterizedGlyph::advance`] contains the glyph's
+    /// vertical advance. Fonts without vertical metrics advance by the em size.
+    pub fn set_vertical(&mut self, vertical: bool) {
+        self.vertical = vertical;
+    }
+
+    /// Check if glyphs are laid out vertically.
+    pub fn vertical(&self) -> bool {
+        self.vertical
+    }
+
+    /// Scale the line height reported by [`crate::Rasterize::metrics`].
+    pub fn set_line_height_scale(&mut self, scale: f32) {
+        self.line_height_scale = scale;
+        self.metrics_cache.get_mut().clear();
+    }
+
+    /// Get the scale applied to the line height.
+    pub fn line_height_scale(&self) -> f32 {
+        self.line_height_scale
+    }
+
+    /// Override the metrics reported by [`crate::Rasterize::metrics`].
+    ///
+    /// The overrides take precedence over the line height scale.
+    pub fn set_metrics_override(&mut self, metrics_override: Option<MetricsOverride>) {
+        self.metrics_override = metrics_override;
+        self.metrics_cache.get_mut().clear();
+    }
+
+    /// Get the metrics override.
+    pub fn metrics_override(&self) -> Option<MetricsOverride> {
+        self.metrics_override
+    }
+
+    /// Set the affine transform applied to rasterized glyphs.
+    ///
+    /// The transform is given as `[m11, m12, m21, m22, dx, dy]`, like a `DWRITE_MATRIX`. The
+    /// bitmap and its bearings cover the transformed glyph, but the advance is still reported
+    /// in untransformed space.
+    ///
+    /// Transforms which rotate or skew glyphs disable grid fitting and subpixel rendering, since
+    /// both rely on glyphs being aligned to the pixel grid. Embedded bitmaps of bitmap fonts are
+    /// not transformed.
+    pub fn set_transform(&mut self, transform: Option<[f32; 6]>) {
+        self.transform = transform;
+    }
+
+    /// Get the affine transform applied to rasterized glyphs.
+    pub fn transform(&self) -> Option<[f32; 6]> {
+        self.transform
+    }
+
+    /// Select the font table used for the ascent, descent and line gap of the [`Metrics`].
+    ///
+    /// Fonts without the selected table use the metrics reported by DirectWrite. By default the
+    /// `USE_TYPO_METRICS` flag of the font decides between the tables.
+    pub fn set_line_metrics_source(&mut self, source: LineMetricsSource) {
+        self.line_metrics_source = source;
+        self.metrics_cache.get_mut().clear();
+    }
+
+    /// Get the font table used for the line metrics.
+    pub fn line_metrics_source(&self) -> LineMetricsSource {
+        self.line_metrics_source
+    }
+
+    /// Add transparent padding around the bitmaps of rasterized glyphs.
+    ///
+    /// Bitmaps grow by `padding` pixels on every side, see [`RasterizedGlyph::pad`]. Advances,
+    /// metrics and glyph bounds are not affected. Glyphs have no padding by default.
+    pub fn set_glyph_padding(&mut self, padding: u32) {
+        self.glyph_padding = padding;
+    }
+
+    /// Get the padding around the bitmaps of rasterized glyphs.
+    pub fn glyph_padding(&self) -> u32 {
+        self.glyph_padding
+    }
+
+    /// Set the glyph index of the `.notdef` glyph.
+    ///
+    /// The glyph is rendered for characters missing from all fonts with
+    /// [`MissingGlyphPolicy::NotdefGlyph`], and characters mapped to it are treated as missing.
+    /// Characters without any glyph in the font are always missing. The OpenType specification
+    /// requires `.notdef` to be glyph `0`, which is the default.
+    pub fn set_notdef_index(&mut self, glyph_index: u16) {
+        self.notdef_index = glyph_index;
+    }
+
+    /// Get the glyph index of the `.notdef` glyph.
+    pub fn notdef_index(&self) -> u16 {
+        self.notdef_index
+    }
+
+    /// Choose whether color glyphs use premultiplied alpha.
+    ///
+    /// Color layers are always composited with premultiplied alpha. With `false`, the color
+    /// channels of [`BitmapBuffer::Rgba`] glyphs are divided by their alpha afterwards, for
+    /// renderers blending with straight alpha. Coverage masks are not affected. Color glyphs are
+    /// premultiplied by default.
+    pub fn set_premultiply_alpha(&mut self, premultiply: bool) {
+        self.premultiply_alpha = premultiply;
+    }
+
+    /// Check if color glyphs use premultiplied alpha.
+    pub fn premultiply_alpha(&self) -> bool {
+        self.premultiply_alpha
+    }
+
+    /// Output aliased glyphs as packed [`BitmapBuffer::Bitmap1`] bitmaps.
+    ///
+    /// This only affects glyphs rendered without antialiasing, either with
+    /// [`crate::RenderingMode::Aliased`] or [`AntialiasMode::None`]. Their coverage is thresholded
+    /// with the [`Self::set_mono_threshold`] threshold. Color glyphs keep their RGBA bitmaps, and
+    /// stroked glyphs and distance fields keep their alpha buffers. Disabled by default.
+    pub fn set_mono_output(&mut self, enabled: bool) {
+        self.mono_output = enabled;
+    }
+
+    /// Check if aliased glyphs are output as packed bitmaps.
+    pub fn mono_output(&self) -> bool {
+        self.mono_output
+    }
+
+    /// Set the coverage at which pixels of aliased glyphs are considered covered.
+    ///
+    /// Pixels with a coverage below the threshold are cleared and all others are filled. This
+    /// only applies to the packed bitmaps of [`Self::set_mono_output`]. The default threshold is
+    /// `0x80`.
+    pub fn set_mono_threshold(&mut self, threshold: u8) {
+        self.mono_threshold = threshold;
+    }
+
+    /// Get the coverage at which pixels of aliased glyphs are considered covered.
+    pub fn mono_threshold(&self) -> u8 {
+        self.mono_threshold
+    }
+
+    /// Set the subpixel arrangement of the display for ClearType rendering.
+    ///
+    /// This takes precedence over the pixel geometry of the rendering params. Subpixel glyphs
+    /// are reordered for [`PixelGeometry::Bgr`] and rendered without color fringes for
+    /// [`PixelGeometry::Flat`], grayscale glyphs are not affected.
+    pub fn set_pixel_geometry(&mut self, pixel_geometry: PixelGeometry) {
+        self.pixel_geometry = pixel_geometry;
+    }
+
+    /// Get the subpixel arrangement used for ClearType rendering.
+    pub fn pixel_geometry(&self) -> PixelGeometry {
+        self.pixel_geometry
+    }
+
+    /// Set the antialiasing of rasterized glyphs.
+    ///
+    /// This overrides the antialiasing picked for the [`crate::RenderingMode`], while keeping
+    /// its DirectWrite rendering and measuring modes. The coverage adjustments of the
+    /// [`RenderingParams`] follow the antialiasing. Subpixel order doesn't match the pixel grid
+    /// for rotated glyphs, so these always use grayscale antialiasing instead of ClearType. The
+    /// aliased rendering mode ignores [`AntialiasMode::ClearType`], since it has no subpixel
+    /// coverage.
+    pub fn set_antialias_mode(&mut self, mode: AntialiasMode) {
+        self.antialias_mode = mode;
+    }
+
+    /// Get the antialiasing of rasterized glyphs.
+    pub fn antialias_mode(&self) -> AntialiasMode {
+        self.antialias_mode
+    }
+
+    /// Set the measuring mode used for rasterizing glyphs.
+    ///
+    /// By default the measuring mode follows the [`crate::RenderingMode`], using GDI classic
+    /// metrics for aliased rendering only.
+    pub fn set_measuring_mode(&mut self, mode: MeasuringMode) {
+        self.measuring_mode = mode;
+    }
+
+    /// Get the measuring mode used for rasterizing glyphs.
+    pub fn measuring_mode(&self) -> MeasuringMode {
+        self.measuring_mode
+    }
+
+    /// Set the glyph rendered for characters which are missing from all fonts.
+    ///
+    /// The glyph is still returned as [`Error::MissingGlyph`]. This has no effect on the
+    /// distance fields created by [`Self::get_glyph_sdf`].
+    pub fn set_missing_glyph_policy(&mut self, policy: MissingGlyphPolicy) {
+        self.missing_glyph_policy = policy;
+    }
+
+    /// Get the glyph rendered for characters which are missing from all fonts.
+    pub fn missing_glyph_policy(&self) -> MissingGlyphPolicy {
+        self.missing_glyph_policy
+    }
+
+    /// Enable or disable the system font fallback.
+    ///
+    /// When disabled, characters missing from the requested font are reported as
+    /// [`Error::MissingGlyph`] instead of being rasterized with a system fallback font. This is
+    /// enabled by default.
+    pub fn set_fallback_enabled(&mut self, enabled: bool) {
+        self.fallback_enabled = enabled;
+    }
+
+    /// Check if the system font fallback is enabled.
+    pub fn fallback_enabled(&self) -> bool {
+        self.fallback_enabled
+    }
+
+    /// Enable or disable collecting rasterization statistics.
+    ///
+    /// Statistics are disabled by default. Disabling them discards all statistics which haven't
+    /// been taken yet.
+    pub fn enable_stats(&mut self, enabled: bool) {
+        if !enabled {
+            self.stats = None;
+        } else if self.stats.is_none() {
+            self.stats = Some(Default::default());
+        }
+    }
+
+    /// Check if rasterization statistics are collected.
+    pub fn stats_enabled(&self) -> bool {
+        self.stats.is_some()
+    }
+
+    /// Take the statistics collected since the last call, resetting them.
+    ///
+    /// All statistics are zero while collecting them is disabled.
+    pub fn take_stats(&mut self) -> RasterStats {
+        self.stats.as_mut().map(|stats| mem::take(stats.get_mut())).unwrap_or_default()
+    }
+
+    /// Time a rasterization stage, if statistics are enabled.
+    fn record<T>(
+        &self,
+        stage: fn(&mut RasterStats) -> &mut StageStats,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let stats = match &self.stats {
+            Some(stats) => stats,
+            None => return f(),
+        };
+
+        let start = Instant::now();
+        let result = f();
+
+        let mut stats = stats.borrow_mut();
+        let stage = stage(&mut stats);
+        stage.count += 1;
+        stage.duration += start.elapsed();
+
+        result
+    }
+
+    /// Set the rounding of glyph advances.
+    ///
+    /// This applies to the advance of rasterized glyphs and the advances reported by
+    /// [`Self::glyph_bounds`] and [`Self::shape_and_rasterize`]. Font metrics like
+    /// [`Metrics::average_advance`] are not affected. Advances are rounded to the nearest pixel by
+    /// default.
+    pub fn set_advance_rounding(&mut self, rounding: AdvanceRounding) {
+        self.advance_rounding = rounding;
+    }
+
+    /// Get the rounding of glyph advances.
+    pub fn advance_rounding(&self) -> AdvanceRounding {
+        self.advance_rounding
+    }
+
+    /// Set a font consulted after all other fallback fonts, loaded from the data of a font file.
+    ///
+    /// This is meant for fonts like Unicode's LastResort font, whose glyphs indicate the block of
+    /// a character which is missing from all other fonts. Characters covered by it are rendered
+    /// like with any other fallback font, even when the system font fallback is disabled. Passing
+    /// `None` removes the last resort font.
+    pub fn set_last_resort_font(&mut self, data: Option<Vec<u8>>) -> Result<(), Error> {
+        self.last_resort_font = data.map(font_from_data).transpose()?;
+        Ok(())
+    }
+
+    /// Get the family name of the last resort font.
+    pub fn last_resort_font(&self) -> Option<&str> {
+        self.last_resort_font.as_ref().map(|font| font.family_name.as_str())
+    }
+
+    /// Enable or disable rendering `U+FFFD` for missing characters.
+    ///
+    /// When enabled, characters missing from all fonts are rendered as the replacement character
+    /// instead of the font's `.notdef` glyph, if any font has it. The glyph is still returned
+    /// as [`Error::MissingGlyph`]. This only applies to [`MissingGlyphPolicy::NotdefGlyph`] and
+    /// is enabled by default.
+    pub fn set_replacement_char_fallback(&mut self, enabled: bool) {
+        self.replacement_char_fallback = enabled;
+    }
+
+    /// Check if `U+FFFD` is rendered for missing characters.
+    pub fn replacement_char_fallback(&self) -> bool {
+        self.replacement_char_fallback
+    }
+
+    /// Enable or disable prefix matching of family names.
+    ///
+    /// When enabled, a family name which doesn't match any installed family will resolve to the
+    /// shortest family starting with the requested name. This is disabled by default.
+    pub fn set_fuzzy_family_matching(&mut self, enabled: bool) {
+        self.fuzzy_family_matching = enabled;
+    }
+
+    /// Check if prefix matching of family names is enabled.
+    pub fn fuzzy_family_matching(&self) -> bool {
+        self.fuzzy_family_matching
+    }
+
+    /// Make the fonts of a directory available without installing them.
+    ///
+    /// All `.ttf`, `.otf` and `.ttc` files in the directory are loaded, skipping invalid font
+    /// files with a warning. Their families are found by [`crate::Rasterize::load_font`], taking
+    /// precedence over installed families with the same name. Returns the names of all families
+    /// in the directory.
+    pub fn add_fonts_from_dir(&mut self, dir: &Path) -> Result<Vec<String>, Error> {
+        let entries = fs::read_dir(dir).map_err(|err| Error::PlatformError(err.to_string()))?;
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().and_then(OsStr::to_str).is_some_and(|extension| {
+                    FONT_FILE_EXTENSIONS.iter().any(|ext| extension.eq_ignore_ascii_case(ext))
+                })
+            })
+            .collect();
+        paths.sort();
+
+        let files: Vec<_> = paths
+            .iter()
+            .filter_map(|path| {
+                let file = FontFile::new_from_path(path);
+                if file.is_none() {
+                    warn!("Skipping invalid font file {path:?}");
+                }
+                file
+            })
+            .collect();
+
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let collection = FontCollection::from_loader(CustomFontCollectionLoaderImpl::new(&files));
+        let families =
+            collection.families_iter().filter_map(|family| family.family_name().ok()).collect();
+        self.custom_fonts.push(collection);
+
+        Ok(families)
+    }
+
+    /// Load a font by its full name, like `Cascadia Code SemiLight`.
+    ///
+    /// All installed fonts and fonts added with [`Self::add_fonts_from_dir`] are searched,
+    /// comparing the name while ignoring case and whitespace against the font's full name and the
+    /// combination of its family and face name.
+    ///
+    /// The font is registered under the description of its family and face name, so loading it
+    /// again returns the same [`FontKey`].
+    pub fn load_font_by_full_name(&mut self, full_name: &str) -> Result<FontKey, Error> {
+        let requested = normalize_family_name(full_name);
+
+        let font = self
+            .custom_fonts
+            .iter()
+            .chain(Some(&self.available_fonts))
+            .flat_map(FontCollection::families_iter)
+            .flat_map(|family| {
+                let count = family.get_font_count();
+                (0..count).filter_map(move |idx| family.font(idx).ok())
+            })
+            .find(|font| {
+                let combined_name = format!("{} {}", font.family_name(), font.face_name());
+                font.informational_string(InformationalStringId::FullName)
+                    .into_iter()
+                    .chain(Some(combined_name))
+                    .any(|name| normalize_family_name(&name) == requested)
+            })
+            .ok_or_else(|| Error::FontNameNotFound(full_name.into()))?;
+
+        let desc = FontDesc::new(font.family_name(), Style::Specific(font.face_name()));
+        if let Some(key) = self.keys.get(&desc) {
+            return Ok(*key);
+        }
+
+        let key = self.register_font_face(font)?;
+        self.keys.insert(desc, key);
+
+        Ok(key)
+    }
+
+    /// Find the font of another face inside the file of a font.
+    ///
+    /// Font collection files can contain faces of multiple families, so all collections are
+    /// searched for the font of the face.
+    fn font_in_same_file(&self, font: &dwrote::Font, face_index: u32) -> Option<dwrote::Font> {
+        let face = create_font_face(font).ok()?;
+        if face.get_index() == face_index {
+            return Some(font.clone());
+        }
+
+        let file = face.files().ok()?.into_iter().next()?;
+        let face = file.create_face(face_index, FontSimulations::None as u32).ok()?;
+        self.custom_fonts
+            .iter()
+            .chain(Some(&self.available_fonts))
+            .find_map(|collection| collection.font_from_face(&face).ok())
+    }
+
+    /// Find a font family by name.
+    ///
+    /// If there is no exact match, the family names are compared while ignoring case and
+    /// whitespace, so both `jetbrains mono` and `JetBrainsMono` resolve to `JetBrains Mono`.
+    ///
+    /// The name `system-ui` resolves to the font used by Windows for its user interface.
+    fn find_family(&self, name: &str) -> Option<FontFamily> {
+        let system_ui_name;
+        let name = if name == SYSTEM_UI_FAMILY_NAME {
+            system_ui_name = system_ui_family_name()?;
+            &system_ui_name
+        } else {
+            name
+        };
+
+        let collections = || self.custom_fonts.iter().chain(Some(&self.available_fonts));
+        for collection in collections() {
+            if let Some(family) = collection.font_family_by_name(name).ok().flatten() {
+                return Some(family);
+            }
+        }
+
+        let requested = normalize_family_name(name);
+        if requested.is_empty() {
+            return None;
+        }
+
+        let mut prefix_match: Option<(usize, FontFamily)> = None;
+        for family in collections().flat_map(FontCollection::families_iter) {
+            let family_name = match family.family_name() {
+                Ok(family_name) => normalize_family_name(&family_name),
+                Err(_) => continue,
+            };
+
+            if family_name == requested {
+                return Some(family);
+            }
+
+            // Prefer the shortest family name to avoid picking a distant variant.
+            if self.fuzzy_family_matching
+                && family_name.starts_with(&requested)
+                && prefix_match.as_ref().map_or(true, |(len, _)| family_name.len() < *len)
+            {
+                prefix_match = Some((family_name.len(), family));
+            }
+        }
+
+        let (_, family) = prefix_match?;
+        debug!("Using family {:?} for requested family {:?}", family.family_name(), name);
+
+        Some(family)
+    }
+
+    fn get_loaded_font(&self, font_key: FontKey) -> Result<&Font, Error> {
+        self.fonts.get(&font_key).ok_or(Error::UnknownFontKey)
+    }
+
+    fn get_glyph_index(&self, face: &FontFace, character: char) -> u16 {
+        self.record(
+            |stats| &mut stats.glyph_index_lookups,
+            || {
+                let glyph_index = face
+                    .glyph_indices(&[character as u32])
+                    .ok()
+                    .and_then(|v| v.first().copied())
+                    .unwrap_or(MISSING_GLYPH_INDEX);
+
+                // DirectWrite only reads a single `cmap` subtable, which can miss characters
+                // outside of the BMP when they're only mapped by another full repertoire
+                // subtable.
+                if glyph_index == MISSING_GLYPH_INDEX && u32::from(character) > 0xFFFF {
+                    return face
+                        .font_table(tables::CMAP)
+                        .ok()
+                        .flatten()
+                        .and_then(|cmap| tables::full_repertoire_glyph(&cmap, character))
+                        .unwrap_or(MISSING_GLYPH_INDEX);
+                }
+
+                glyph_index
+            },
+        )
+    }
+
+    /// Get the glyph index of a variation sequence.
+    ///
+    /// Returns `None` if the font has no glyph for the sequence.
+    fn get_variation_glyph_index(
+        &self,
+        face: &FontFace,
+        character: char,
+        selector: char,
+    ) -> Option<u16> {
+        let cmap = face.font_table(tables::CMAP).ok()??;
+
+        match tables::variation_glyph(&cmap, character, selector)? {
+            VariationGlyph::Glyph(glyph_index) => Some(glyph_index),
+            VariationGlyph::Default => Some(self.get_glyph_index(face, character))
+                .filter(|&glyph_index| glyph_index != MISSING_GLYPH_INDEX),
+        }
+    }
+
+    /// Find a system fallback font for a character, reusing previously resolved fonts.
+    fn cached_fallback_font(
+        &self,
+        font_key: FontKey,
+        loaded_font: &Font,
+        character: char,
+        selector: Option<char>,
+    ) -> Option<Font> {
+        if !self.fallback_enabled {
+            return None;
+        }
+
+        self.fallback_cache
+            .borrow_mut()
+            .fonts
+            .entry((font_key, character, selector))
+            .or_insert_with(|| {
+                // Fallback fonts whose face can't be created are treated like missing fallbacks.
+                self.get_fallback_font(loaded_font, character, selector)
+                    .and_then(|fallback_font| Font::try_from(fallback_font).ok())
+            })
+            .clone()
+    }
+
+    fn get_fallback_font(
+        &self,
+        loaded_font: &Font,
+        character: char,
+        selector: Option<char>,
+    ) -> Option<dwrote::Font> {
+        let mut buffer = [0u16; 4];
+        let character_length = character.encode_utf16(&mut buffer).len();
+        let selector_length = selector
+            .map_or(0, |selector| selector.encode_utf16(&mut buffer[character_length..]).len());
+
+        // Include the variation selector, so DirectWrite can pick a font for the presentation.
+        let utf16_codepoints = &buffer[..character_length + selector_length];
+
+        self.map_fallback_font(loaded_font, utf16_codepoints, character_length)
+    }
+
+    /// Find a system fallback font for the start of a UTF-16 text.
+    ///
+    /// Returns `None` unless the font covers at least the first `required_length` code units.
+    fn map_fallback_font(
+        &self,
+        loaded_font: &Font,
+        text: &[u16],
+        required_length: usize,
+    ) -> Option<dwrote::Font> {
+        if !self.fallback_enabled {
+            return None;
+        }
+
+        let fallback = self.fallback_sequence.as_ref()?;
+
+        let length = text.len() as u32;
+
+        let characters = char::decode_utf16(text.iter().copied()).filter_map(Result::ok);
+        let direction = reading_direction(characters);
+
+        let text_analysis_source_data =
+            TextAnalysisSourceData { locale: &self.locale, length, direction };
+        let text_analysis_source =
+            TextAnalysisSource::from_text(Box::new(text_analysis_source_data), Cow::Borrowed(text));
+
+        let fallback_result = self.record(
+            |stats| &mut stats.fallback_lookups,
+            || {
+                fallback.map_characters(
+                    &text_analysis_source,
+                    0,
+                    length,
+                    &self.available_fonts,
+                    Some(&loaded_font.family_name),
+                    loaded_font.weight,
+                    loaded_font.style,
+                    loaded_font.stretch,
+                )
+            },
+        );
+
+        // Astral characters are encoded as surrogate pairs, which must be mapped as a whole.
+        if fallback_result.mapped_length < required_length {
+            return None;
+        }
+
+        fallback_result.mapped_font
+    }
+}
+
+impl crate::Rasterize for DirectWriteRasterizer {
+    fn new() -> Result<DirectWriteRasterizer, Error> {
+        Ok(DirectWriteRasterizer {
+            fonts: HashMap::new(),
+            keys: HashMap::new(),
+            available_fonts: FontCollection::system(),
+            custom_fonts: Vec::new(),
+            fallback_sequence: FontFallback::get_system_fallback(),
+            fallback_enabled: true,
+            last_resort_font: None,
+            replacement_char_fallback: true,
+            advance_rounding: Default::default(),
+            stats: None,
+            rendering_mode: Default::default(),
+            background_opaque: None,
+            grid_fitting: false,
+            grid_fit_threshold: None,
+            fuzzy_family_matching: false,
+            line_height_scale: 1.,
+            metrics_override: None,
+            missing_glyph_policy: Default::default(),
+            notdef_index: MISSING_GLYPH_INDEX,
+            line_metrics_source: Default::default(),
+            antialias_mode: Default::default(),
+            measuring_mode: Default::default(),
+            transform: None,
+            vertical: false,
+            color_palette: 0,
+            stem_darkening: None,
+            synthetic_smallcaps: None,
+            grayscale_params: RenderingParams::GRAYSCALE,
+            cleartype_params: RenderingParams::CLEARTYPE,
+            display_params: Default::default(),
+            pixel_geometry: Default::default(),
+            linear_coverage: false,
+            glyph_padding: 0,
+            premultiply_alpha: true,
+            mono_output: false,
+            mono_threshold: 0x80,
+            metrics_cache: Default::default(),
+            fallback_cache: Default::default(),
+            locale: get_current_locale(),
+        })
+    }
+
+    fn set_rendering_mode(&mut self, mode: super::RenderingMode) {
+        self.rendering_mode = mode;
+    }
+
+    fn set_grid_fitting(&mut self, enabled: bool) {
+        self.grid_fitting = enabled;
+    }
+
+    fn set_background_is_opaque(&mut self, opaque: bool) {
+        self.background_opaque = Some(opaque);
+    }
+
+    fn metrics(&self, key: FontKey, size: Size) -> Result<Metrics, Error> {
+        if let Some(metrics) = self.metrics_cache.borrow().get(&(key, size)) {
+            return Ok(*metrics);
+        }
+
+        let metrics = self.compute_metrics(key, size)?;
+        self.metrics_cache.borrow_mut().insert((key, size), metrics);
+
+        Ok(metrics)
+    }
+
+    fn load_font(&mut self, desc: &FontDesc, _size: Size) -> Result<FontKey, Error> {
+        // Fast path if face is already loaded.
+        if let Some(key) = self.keys.get(desc) {
+            return Ok(*key);
+        }
+
+        let family =
+            self.find_family(&desc.name).ok_or_else(|| Error::FontNotFound(desc.clone()))?;
+
+        let (font, mut variations) = match &desc.style {
+            &Style::Description { weight, slant } => {
+                // DirectWrite matching treats italic and oblique faces as equivalent, so true
+                // oblique faces are looked up explicitly.
+                let oblique_font = match slant {
+                    Slant::Oblique => find_oblique_font(&family, weight.into()),
+                    _ => None,
+                };
+
+                // This searches for the "best" font - should mean we don't have to worry about
+                // fallbacks if our exact desired weight/style isn't available.
+                let stretch = desc.stretch.map_or(FontStretch::Normal, FontStretch::from_u32);
+                // DirectWrite may simulate one of the styles on a related face, so real faces
+                // matching both weight and slant, like Bold Italic, are preferred explicitly.
+                let font = match oblique_font
+                    .or_else(|| find_exact_font(&family, weight.into(), stretch, slant.into()))
+                {
+                    Some(font) => font,
+                    None => family
+                        .first_matching_font(weight.into(), stretch, slant.into())
+                        .map_err(|_| Error::FontNotFound(desc.clone()))?,
+                };
+                (font, Vec::new())
+            },
+            Style::Specific(style) => {
+                let count = family.get_font_count();
+                let fonts = || (0..count).filter_map(|idx| family.font(idx).ok());
+
+                match fonts().find(|f| f.face_name() == *style) {
+                    Some(font) => (font, Vec::new()),
+                    // Fall back to the named instances of variable fonts.
+                    None => fonts()
+                        .find_map(|font| {
+                            let face = create_font_face(&font).ok()?;
+                            let variations = find_named_instance(&face, style)?;
+                            Some((font, variations))
+                        })
+                        .ok_or_else(|| Error::FontNotFound(desc.clone()))?,
+                }
+            },
+        };
+
+        // Face indices select a face inside the file of the font matching the style.
+        let font = match desc.face_index {
+            Some(face_index) => {
+                variations.clear();
+                self.font_in_same_file(&font, face_index)
+                    .ok_or_else(|| Error::FontNotFound(desc.clone()))?
+            },
+            None => font,
+        };
+
+        let mut font = Font::try_from(font)?;
+        variations.extend_from_slice(&desc.variations);
+        if !variations.is_empty() {
+            font.face = instantiate_variations(&font.face, &variations)?;
+        }
+
+        let key = FontKey::next();
+        self.keys.insert(desc.clone(), key);
+        self.fonts.insert(key, font);
+
+        Ok(key)
+    }
+
+    fn get_glyph(&mut self, glyph: GlyphKey) -> Result<RasterizedGlyph, Error> {
+        let settings = self.rasterization_settings()?;
+        self.get_glyph_with_settings(&settings, glyph, None)
+    }
+
+    fn kerning(&mut self, _left: GlyphKey, _right: GlyphKey) -> (f32, f32) {
+        (0., 0.)
+    }
+}
+
+impl fmt::Debug for DirectWriteRasterizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fonts: HashMap<_, _> = self
+            .fonts
+            .iter()
+            .map(|(key, font)| {
+                (key, format!("{} ({:?}, {:?})", font.family_name, font.weight, font.style))
+            })
+            .collect();
+
+        f.debug_struct("DirectWriteRasterizer")
+            .field("font_count", &self.fonts.len())
+            .field("fonts", &fonts)
+            .field("rendering_mode", &self.rendering_mode)
+            .field("grid_fitting", &self.grid_fitting)
+            .finish_non_exhaustive()
+    }
+}
+
+// SAFETY: The rasterizer exclusively owns its DirectWrite handles, which are all free-threaded.
+unsafe impl Send for DirectWriteRasterizer {}
+
+impl TryFrom<dwrote::Font> for Font {
+    type Error = Error;
+
+    fn try_from(font: dwrote::Font) -> Result<Font, Error> {
+        let face = create_font_face(&font)?;
+        let is_color = color::is_color_font(&face);
+        let has_outlines = outline::has_outlines(&face)?;
+        let bitmap_tables = bitmap::BitmapTables::find(&face)?;
+
+        Ok(Font {
+            face,
+            family_name: font.family_name(),
+            weight: font.weight(),
+            style: font.style(),
+            stretch: font.stretch(),
+            is_color,
+            has_outlines,
+            bitmap_tables,
+        })
+    }
+}
+
+/// Load the first font from the data of a font file.
+fn font_from_data(data: Vec<u8>) -> Result<Font, Error> {
+    let file = FontFile::new_from_buffer(Arc::new(data))
+        .ok_or(Error::Unsupported("loading fonts from this data"))?;
+    let collection = FontCollection::from_loader(CustomFontCollectionLoaderImpl::new(&[file]));
+    let family = collection.font_family(0)?;
+    Font::try_from(family.font(0)?)
+}
+
+/// Create the font face of a font.
+///
+/// Unlike [`dwrote::Font::create_font_face`], this doesn't panic when the face can't be created,
+/// like for font files which were removed after the font collection was created.
+fn create_font_face(font: &dwrote::Font) -> Result<FontFace, Error> {
+    unsafe {
+        let mut face: *mut IDWriteFontFace = ptr::null_mut();
+        let hr = (*font.as_ptr()).CreateFontFace(&mut face);
+        if hr != S_OK || face.is_null() {
+            return Err(Error::from(hr));
+        }
+
+        Ok(FontFace::take(ComPtr::from_raw(face)))
+    }
+}
+
+/// Font table borrowed from a font face.
+///
+/// Unlike [`FontFace::font_table`], this doesn't copy the table's data.
+struct FontTable<'a> {
+    face: &'a FontFace,
+    data: &'a [u8],
+    context: *mut c_void,
+}
+
+impl<'a> FontTable<'a> {
+    /// Borrow a table of a font face, returning `None` if the font doesn't have it.
+    fn new(font_face: &'a FontFace, tag: u32) -> Result<Option<Self>, Error> {
+        let mut data: *const c_void = ptr::null();
+        let mut size = 0;
+        let mut context = ptr::null_mut();
+        let mut exists = 0;
+
+        unsafe {
+            let face = font_face.as_ptr();
+            let hr = (*face).TryGetFontTable(tag, &mut data, &mut size, &mut context, &mut exists);
+            if hr != S_OK {
+                return Err(Error::from(hr));
+            }
+
+            if exists == 0 {
+                return Ok(None);
+            }
+
+            let data = if data.is_null() {
+                &[][..]
+            } else {
+                slice::from_raw_parts(data as *const u8, size as usize)
+            };
+
+            Ok(Some(FontTable { face: font_face, data, context }))
+        }
+    }
+
+    /// Check if a font face has a table.
+    fn exists(face: &FontFace, tag: u32) -> Result<bool, Error> {
+        Ok(FontTable::new(face, tag)?.is_some())
+    }
+}
+
+impl Deref for FontTable<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl Drop for FontTable<'_> {
+    fn drop(&mut self) {
+        unsafe { (*self.face.as_ptr()).ReleaseFontTable(self.context) };
+    }
+}
+
+impl From<Weight> for FontWeight {
+    fn from(weight: Weight) -> FontWeight {
+        match weight {
+            Weight::Bold => FontWeight::Bold,
+            Weight::Normal => FontWeight::Regular,
+        }
+    }
+}
+
+impl From<Slant> for FontStyle {
+    fn from(slant: Slant) -> FontStyle {
+        match slant {
+            Slant::Oblique => FontStyle::Oblique,
+            Slant::Italic => FontStyle::Italic,
+            Slant::Normal => FontStyle::Normal,
+        }
+    }
+}
+
+impl From<FontStyle> for Slant {
+    fn from(style: FontStyle) -> Slant {
+        match style {
+            FontStyle::Oblique => Slant::Oblique,
+            FontStyle::Italic => Slant::Italic,
+            FontStyle::Normal => Slant::Normal,
+        }
+    }
+}
+
+/// Find the non-synthetic font of a family matching weight, stretch and style exactly.
+fn find_exact_font(
+    family: &FontFamily,
+    weight: FontWeight,
+    stretch: FontStretch,
+    style: FontStyle,
+) -> Option<dwrote::Font> {
+    (0..family.get_font_count()).filter_map(|idx| family.font(idx).ok()).find(|font| {
+        font.simulations() == FontSimulations::None
+            && font.weight() == weight
+            && font.stretch() == stretch
+            && font.style() == style
+    })
+}
+
+/// Find the non-synthetic oblique font of a family closest to the requested weight.
+fn find_oblique_font(family: &FontFamily, weight: FontWeight) -> Option<dwrote::Font> {
+    let fonts: Vec<_> = (0..family.get_font_count())
+        .filter_map(|idx| family.font(idx).ok())
+        .filter(|font| font.simulations() == FontSimulations::None)
+        .collect();
+
+    let index = closest_oblique(fonts.iter().map(|font| (font.style(), font.weight())), weight)?;
+    fonts.into_iter().nth(index)
+}
+
+/// Get the index of the oblique style closest to the requested weight.
+fn closest_oblique(
+    styles: impl Iterator<Item = (FontStyle, FontWeight)>,
+    weight: FontWeight,
+) -> Option<usize> {
+    styles
+        .enumerate()
+        .filter(|(_, (style, _))| *style == FontStyle::Oblique)
+        .min_by_key(|(_, (_, font_weight))| font_weight.to_u32().abs_diff(weight.to_u32()))
+        .map(|(index, _)| index)
+}
+
+/// Increase the contrast of glyph coverage, like DirectWrite's enhanced contrast.
+fn enhance_contrast(coverage: &mut [u8], contrast: f32) {
+    if contrast <= 0. {
+        return;
+    }
+
+    let mut lookup = [0; 256];
+    for (value, enhanced) in lookup.iter_mut().enumerate() {
+        let alpha = value as f32 / 255.;
+        *enhanced = (alpha * (contrast + 1.) / (alpha * contrast + 1.) * 255.).round() as u8;
+    }
+
+    for value in coverage {
+        *value = lookup[usize::from(*value)];
+    }
+}
+
+/// Convert gamma-encoded glyph coverage to linear coverage.
+fn linearize_coverage(coverage: &mut [u8], gamma: f32) {
+    if gamma == 1. {
+        return;
+    }
+
+    let mut lookup = [0; 256];
+    for (value, linear) in lookup.iter_mut().enumerate() {
+        *linear = ((value as f32 / 255.).powf(gamma) * 255.).round() as u8;
+    }
+
+    for value in coverage {
+        *value = lookup[usize::from(*value)];
+    }
+}
+
+/// Boost the coverage of small glyphs to make thin stems more visible.
+///
+/// The darkening fades out linearly and stops completely at `STEM_DARKENING_MAX_SIZE`.
+fn darken_stems(coverage: &mut [u8], em_size: f32, amount: f32) {
+    let strength = amount * (1. - em_size / STEM_DARKENING_MAX_SIZE).clamp(0., 1.);
+    if strength <= 0. {
+        return;
+    }
+
+    let exponent = 1. / (1. + strength);
+    let mut lookup = [0; 256];
+    for (value, darkened) in lookup.iter_mut().enumerate() {
+        *darkened = ((value as f32 / 255.).powf(exponent) * 255.).round() as u8;
+    }
+
+    for value in coverage {
+        *value = lookup[usize::from(*value)];
+    }
+}
+
+/// Get the em size in pixels, rejecting sizes DirectWrite can't rasterize.
+fn em_size(size: Size) -> Result<f32, Error> {
+    let em_size = size.as_px();
+    if em_size > 0. {
+        Ok(em_size)
+    } else {
+        Err(Error::InvalidSize(size))
+    }
+}
+
+/// Get the factor converting design units to pixels.
+fn design_unit_scale(design_units_per_em: u16, size: Size) -> f64 {
+    f64::from(size.as_px()) / f64::from(design_units_per_em)
+}
+
+/// Analyze a glyph run for rasterization.
+fn glyph_run_analysis(
+    settings: &RasterizationSettings,
+    glyph_run: &DWRITE_GLYPH_RUN,
+    antialias_mode: DWRITE_TEXT_ANTIALIAS_MODE,
+    (baseline_x, baseline_y): (f32, f32),
+) -> Result<GlyphRunAnalysis, Error> {
+    unsafe {
+        let mut native: *mut IDWriteGlyphRunAnalysis = ptr::null_mut();
+        let hr = settings.factory3.CreateGlyphRunAnalysis(
+            glyph_run,
+            settings.transform.as_ref().map_or(ptr::null(), |transform| transform),
+            settings.rendering_mode,
+            settings.measuring_mode,
+            settings.grid_fit_mode(glyph_run.fontEmSize),
+            antialias_mode,
+            baseline_x,
+            baseline_y,
+            &mut native,
+        );
+        if hr != S_OK || native.is_null() {
+            info!("DWrite3 CreateGlyphRunAnalysis failed: hr={:X}", hr);
+            return Err(Error::from(hr));
+        }
+        Ok(GlyphRunAnalysis::take(ComPtr::from_raw(native)))
+    }
+}
+
+/// Advance and horizontal side bearings of a glyph in pixels.
+type GlyphMetrics = ((i32, i32), (f32, f32));
+
+/// Get the advance and the horizontal side bearings of a glyph in pixels.
+///
+/// The vertical advance is only computed for `vertical` text and zero otherwise.
+fn glyph_metrics(
+    face: &FontFace,
+    glyph_index: u16,
+    em_size: f32,
+    vertical: bool,
+    rounding: AdvanceRounding,
+) -> Result<GlyphMetrics, Error> {
+    let glyph_metrics = face.design_glyph_metrics(&[glyph_index], false)?;
+    let glyph_metrics = glyph_metrics.first().ok_or(Error::MetricsNotFound)?;
+
+    let design_units_per_em = face.metrics().metrics0().designUnitsPerEm;
+    let scale = f64::from(em_size) / f64::from(design_units_per_em);
+    let horizontal_advance = scale_advance(glyph_metrics.advanceWidth, scale, rounding) as i32;
+
+    let vertical_advance = if !vertical {
+        0
+    } else if face.font_table(tables::VHEA)?.is_some() {
+        scale_advance(glyph_metrics.advanceHeight, scale, rounding) as i32
+    } else {
+        // Fonts without vertical metrics use the em size for every glyph.
+        scale_advance(u32::from(design_units_per_em), scale, rounding) as i32
+    };
+
+    let side_bearings = (
+        (f64::from(glyph_metrics.leftSideBearing) * scale) as f32,
+        (f64::from(glyph_metrics.rightSideBearing) * scale) as f32,
+    );
+
+    Ok(((horizontal_advance, vertical_advance), side_bearings))
+}
+
+/// Convert an advance from design units to pixels, applying the advance rounding.
+fn scale_advance(advance: u32, scale: f64, rounding: AdvanceRounding) -> f32 {
+    rounding.apply((f64::from(advance) * scale) as f32)
+}
+
+/// Create an instance of a variable font face at the given axis coordinates.
+///
+/// Axes which aren't part of `variations` keep their current values.
+fn instantiate_variations(
+    face: &FontFace,
+    variations: &[FontVariation],
+) -> Result<FontFace, Error> {
+    let mut axis_values: Vec<DWRITE_FONT_AXIS_VALUE> = face.variations()?;
+
+    for variation in variations {
+        let axis_tag = u32::from_le_bytes(variation.tag);
+        let axis_value = axis_values
+            .iter_mut()
+            .find(|axis_value| axis_value.axisTag == axis_tag)
+            .ok_or(Error::UnknownVariationAxis(variation.tag))?;
+        axis_value.value = variation.value;
+    }
+
+    face.create_font_face_with_variations(face.simulations() as u32, &axis_values)
+        .ok_or_else(|| Error::PlatformError("unable to instantiate font variations".into()))
+}
+
+/// Find the axis coordinates of a variable font's named instance, like `SemiBold`.
+fn find_named_instance(face: &FontFace, name: &str) -> Option<Vec<FontVariation>> {
+    if !face.has_variations() {
+        return None;
+    }
+
+    let fvar = face.font_table(tables::FVAR).ok().flatten()?;
+    let names = face.font_table(tables::NAME).ok().flatten()?;
+
+    tables::fvar_instances(&fvar)
+        .into_iter()
+        .find(|instance| {
+            tables::name_string(&names, instance.name_id)
+                .is_some_and(|instance_name| instance_name.eq_ignore_ascii_case(name))
+        })
+        .map(|instance| instance.coordinates)
+}
+
+/// Normalize a family name for case and whitespace insensitive comparison.
+fn normalize_family_name(name: &str) -> String {
+    name.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect()
+}
+
+/// Locale used when the user's default locale can't be determined.
+const FALLBACK_LOCALE: &str = "en-US";
+
+fn get_current_locale() -> String {
+    static WARN_INVALID_LOCALE: Once = Once::new();
+
+    let mut buffer = vec![0u16; LOCALE_NAME_MAX_LENGTH];
+    let len = unsafe { GetUserDefaultLocaleName(buffer.as_mut_ptr(), buffer.len() as i32) };
+
+    // `len` includes null byte, which we don't need in Rust. It is zero on failure.
+    let locale = usize::try_from(len)
+        .ok()
+        .and_then(|len| buffer.get(..len.checked_sub(1)?))
+        .and_then(|locale| OsString::from_wide(locale).into_string().ok());
+
+    locale.unwrap_or_else(|| {
+        WARN_INVALID_LOCALE.call_once(|| {
+            warn!("Unable to get the user's locale, falling back to {FALLBACK_LOCALE}");
+        });
+        FALLBACK_LOCALE.into()
+    })
+}
+
+/// Check if a character belongs to a script written from right to left.
+fn is_right_to_left(character: char) -> bool {
+    matches!(
+        character as u32,
+        // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic and their extensions.
+        0x0590..=0x08FF
+            // Hebrew and Arabic presentation forms.
+            | 0xFB1D..=0xFDFF
+            | 0xFE70..=0xFEFF
+            // Historic right-to-left scripts, Adlam and Arabic mathematical symbols.
+            | 0x10800..=0x10FFF
+            | 0x1E800..=0x1EFFF
+    )
+}
+
+/// Determine the paragraph direction of a text from its first alphabetic character.
+fn reading_direction(text: impl IntoIterator<Item = char>) -> dwrite::DWRITE_READING_DIRECTION {
+    match text.into_iter().find(|character| character.is_alphabetic()) {
+        Some(character) if is_right_to_left(character) => {
+            dwrite::DWRITE_READING_DIRECTION_RIGHT_TO_LEFT
+        },
+        _ => dwrite::DWRITE_READING_DIRECTION_LEFT_TO_RIGHT,
+    }
+}
+
+/// Check if ClearType font smoothing is enabled in the system settings.
+///
+/// This can be used to pick a sensible default [`crate::RenderingMode`], using subpixel
+/// rendering only when the user hasn't disabled ClearType.
+pub fn system_cleartype_enabled() -> bool {
+    let mut smoothing: BOOL = 0;
+    let mut smoothing_type: UINT = 0;
+
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETFONTSMOOTHING,
+            0,
+            &mut smoothing as *mut BOOL as *mut c_void,
+            0,
+        ) != 0
+            && smoothing != 0
+            && SystemParametersInfoW(
+                SPI_GETFONTSMOOTHINGTYPE,
+                0,
+                &mut smoothing_type as *mut UINT as *mut c_void,
+                0,
+            ) != 0
+            && smoothing_type == FE_FONTSMOOTHINGCLEARTYPE
+    }
+}
+
+/// Get the family name of the system UI font.
+fn system_ui_family_name() -> Option<String> {
+    let mut metrics = NONCLIENTMETRICSW {
+        cbSize: mem::size_of::<NONCLIENTMETRICSW>() as u32,
+        ..Default::default()
+    };
+
+    let success = unsafe {
+        SystemParametersInfoW(
+            SPI_GETNONCLIENTMETRICS,
+            metrics.cbSize,
+            &mut metrics as *mut NONCLIENTMETRICSW as *mut c_void,
+            0,
+        )
+    };
+    if success == 0 {
+        return None;
+    }
+
+    let face_name = &metrics.lfMessageFont.lfFaceName;
+    let len = face_name.iter().position(|&c| c == 0).unwrap_or(face_name.len());
+    OsString::from_wide(&face_name[..len]).into_string().ok()
+}
+
+/// Font fallback information for dwrote's TextAnalysisSource.
+struct TextAnalysisSourceData<'a> {
+    locale: &'a str,
+    length: u32,
+    direction: dwrite::DWRITE_READING_DIRECTION,
+}
+
+impl TextAnalysisSourceMethods for TextAnalysisSourceData<'_> {
+    fn get_locale_name(&self, _text_position: u32) -> (Cow<'_, str>, u32) {
+        (Cow::Borrowed(self.locale), self.length)
+    }
+
+    fn get_paragraph_reading_direction(&self) -> dwrite::DWRITE_READING_DIRECTION {
+        self.direction
+    }
 }
 
 impl From<HRESULT> for Error {
@@ -439,3 +3290,1214 @@ impl From<HRESULT> for Error {
         Error::PlatformError(message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rasterize;
+
+    fn regular(family: &str) -> FontDesc {
+        FontDesc::new(family, Style::Description { slant: Slant::Normal, weight: Weight::Normal })
+    }
+
+    fn consolas(rasterizer: &mut DirectWriteRasterizer, size: Size) -> FontKey {
+        rasterizer.load_font(&regular("Consolas"), size).unwrap()
+    }
+
+    #[test]
+    fn rasterize_on_other_thread() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = std::thread::spawn(move || {
+            rasterizer.get_glyph(GlyphKey { character: 'a', font_key, size })
+        });
+
+        let glyph = glyph.join().unwrap().unwrap();
+        assert!(glyph.width > 0 && glyph.height > 0);
+    }
+
+    #[test]
+    fn oblique_preferred_over_italic() {
+        let styles = [
+            (FontStyle::Normal, FontWeight::Regular),
+            (FontStyle::Italic, FontWeight::Regular),
+            (FontStyle::Oblique, FontWeight::Bold),
+            (FontStyle::Oblique, FontWeight::Regular),
+        ];
+
+        let index = closest_oblique(styles.into_iter(), FontWeight::Regular);
+        assert_eq!(index, Some(3));
+
+        let index = closest_oblique(styles[..2].iter().copied(), FontWeight::Regular);
+        assert_eq!(index, None);
+    }
+
+    #[test]
+    fn oblique_face_preferred_over_italic_face() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let style = Style::Description { slant: Slant::Italic, weight: Weight::Normal };
+        let font_key = rasterizer.load_font(&FontDesc::new("Consolas", style), size).unwrap();
+        let italic_path = rasterizer.font_path(font_key).unwrap().unwrap();
+
+        // No installed family ships both faces, so an oblique copy of the italic face is added.
+        let dir = std::env::temp_dir().join("crossfont-oblique-and-italic");
+        fs::create_dir_all(&dir).unwrap();
+        let mut font = fs::read(italic_path).unwrap();
+        fs::write(dir.join("italic.ttf"), &font).unwrap();
+        make_oblique(&mut font);
+        fs::write(dir.join("oblique.ttf"), &font).unwrap();
+        rasterizer.add_fonts_from_dir(&dir).unwrap();
+        rasterizer.clear();
+
+        for (slant, file_name) in [(Slant::Oblique, "oblique.ttf"), (Slant::Italic, "italic.ttf")] {
+            let style = Style::Description { slant, weight: Weight::Normal };
+            let font_key = rasterizer.load_font(&FontDesc::new("Consolas", style), size).unwrap();
+            assert_eq!(rasterizer.resolved_style(font_key).unwrap().slant, slant);
+
+            let path = rasterizer.font_path(font_key).unwrap().unwrap();
+            assert_eq!(path.file_name(), Some(OsStr::new(file_name)));
+        }
+
+        drop(rasterizer);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    /// Turn an italic font file into an oblique face named `Sloped`.
+    fn make_oblique(font: &mut [u8]) {
+        let num_tables = tables::read_u16(font, 4).unwrap();
+        for record in (0..usize::from(num_tables)).map(|index| 12 + index * 16) {
+            let tag: [u8; 4] = font[record..record + 4].try_into().unwrap();
+            let offset = tables::read_u32(font, record + 8).unwrap() as usize;
+            let length = tables::read_u32(font, record + 12).unwrap() as usize;
+            let table = &mut font[offset..offset + length];
+
+            match &tag {
+                b"OS/2" => {
+                    // The oblique flag requires at least version 4, which has the same layout
+                    // as versions 2 and 3.
+                    table[..2].copy_from_slice(&4u16.to_be_bytes());
+                    let selection = u16::from_be_bytes([table[62], table[63]]);
+                    let selection = (selection & !0x1) | 0x200;
+                    table[62..64].copy_from_slice(&selection.to_be_bytes());
+                },
+                b"name" => {
+                    let utf16 = |text: &str| -> Vec<u8> {
+                        text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+                    };
+                    replace_bytes(table, b"Italic", b"Sloped");
+                    replace_bytes(table, &utf16("Italic"), &utf16("Sloped"));
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Replace all occurrences of a byte string with another one of the same length.
+    fn replace_bytes(data: &mut [u8], from: &[u8], to: &[u8]) {
+        let mut index = 0;
+        while index + from.len() <= data.len() {
+            if data[index..].starts_with(from) {
+                data[index..index + to.len()].copy_from_slice(to);
+                index += from.len();
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn stem_darkening() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let style = Style::Specific("Light".into());
+        let size = Size::new(8.);
+        let font_key = rasterizer.load_font(&FontDesc::new("Segoe UI", style), size).unwrap();
+        let glyph = GlyphKey { character: 'l', font_key, size };
+
+        let average_coverage = |glyph: RasterizedGlyph| {
+            let BitmapBuffer::Rgb(buffer) = glyph.buffer else { panic!("expected RGB buffer") };
+            buffer.iter().map(|&value| f32::from(value)).sum::<f32>() / buffer.len() as f32
+        };
+
+        let regular = average_coverage(rasterizer.get_glyph(glyph).unwrap());
+        rasterizer.set_stem_darkening(true, 1.);
+        let darkened = average_coverage(rasterizer.get_glyph(glyph).unwrap());
+
+        assert!(darkened > regular);
+    }
+
+    #[test]
+    fn fallback_for_astral_character() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: '\u{1F680}', font_key, size });
+
+        let glyph = glyph.unwrap();
+        assert!(glyph.width > 0 && glyph.height > 0);
+    }
+
+    #[test]
+    fn rasterize_whitespace() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: ' ', font_key, size }).unwrap();
+
+        assert_eq!((glyph.width, glyph.height), (0, 0));
+        assert!(glyph.advance.0 > 0);
+    }
+
+    #[test]
+    fn shaped_clusters_are_byte_offsets() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyphs = rasterizer.shape_and_rasterize("aé b", font_key, size).unwrap();
+
+        let clusters: Vec<usize> = glyphs.iter().map(|glyph| glyph.cluster).collect();
+        assert_eq!(clusters, vec![0, 1, 3, 4]);
+        assert!(glyphs.iter().all(|glyph| glyph.advance > 0.));
+    }
+
+    #[test]
+    fn arabic_fallback_is_right_to_left() {
+        let right_to_left = dwrite::DWRITE_READING_DIRECTION_RIGHT_TO_LEFT;
+        assert_eq!(reading_direction(Some('\u{0627}')), right_to_left);
+        assert_eq!(reading_direction("1 \u{05E9}\u{05DC}".chars()), right_to_left);
+        assert_eq!(reading_direction("a".chars()), dwrite::DWRITE_READING_DIRECTION_LEFT_TO_RIGHT);
+
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: '\u{0627}', font_key, size });
+
+        let glyph = glyph.unwrap();
+        assert!(glyph.width > 0 && glyph.height > 0);
+    }
+
+    #[test]
+    fn color_glyph_query() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let emoji_desc = regular("Segoe UI Emoji");
+        let emoji_key = rasterizer.load_font(&emoji_desc, size).unwrap();
+        let emoji_face = &rasterizer.get_loaded_font(emoji_key).unwrap().face;
+        let rocket = emoji_face.glyph_indices(&[0x1F680]).unwrap()[0];
+        let letter = emoji_face.glyph_indices(&['a' as u32]).unwrap()[0];
+        assert!(rasterizer.is_color_glyph(emoji_key, rocket).unwrap());
+        assert!(!rasterizer.is_color_glyph(emoji_key, letter).unwrap());
+
+        let font_key = consolas(&mut rasterizer, size);
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+        let glyph_index = face.glyph_indices(&['a' as u32]).unwrap()[0];
+        assert!(!rasterizer.is_color_glyph(font_key, glyph_index).unwrap());
+    }
+
+    #[test]
+    fn aliased_coverage_is_binary() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Aliased);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: 'a', font_key, size }).unwrap();
+
+        let buffer = match glyph.buffer {
+            BitmapBuffer::Rgb(buffer) => buffer,
+            _ => panic!("expected RGB glyph"),
+        };
+        assert!(!buffer.is_empty());
+        assert!(buffer.iter().all(|&value| value == 0x00 || value == 0xFF));
+    }
+
+    #[test]
+    fn side_bearings_match_bounds() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let style = Style::Description { slant: Slant::Italic, weight: Weight::Normal };
+        let size = Size::new(12.);
+        let font_desc = FontDesc::new("Times New Roman", style);
+        let font_key = rasterizer.load_font(&font_desc, size).unwrap();
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+        let glyph_index = face.glyph_indices(&['f' as u32]).unwrap()[0];
+
+        let (left, right) = rasterizer.glyph_side_bearings(font_key, glyph_index, size).unwrap();
+        let bounds = rasterizer.glyph_bounds(font_key, glyph_index, size).unwrap();
+
+        assert!((left - bounds.left).abs() < 0.01);
+        assert!((bounds.advance - right - bounds.right).abs() < 0.01);
+    }
+
+    #[test]
+    fn system_ui_family() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let font_key = rasterizer.load_font(&regular("system-ui"), size).unwrap();
+
+        let family_name = &rasterizer.get_loaded_font(font_key).unwrap().family_name;
+        assert_eq!(Some(family_name), system_ui_family_name().as_ref());
+    }
+
+    #[test]
+    fn raw_metrics_are_unscaled() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(8.);
+        let font_key = consolas(&mut rasterizer, size);
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+        let glyph_index = face.glyph_indices(&['a' as u32]).unwrap()[0];
+
+        let bounds = rasterizer.glyph_bounds(font_key, glyph_index, size).unwrap();
+        let raw = rasterizer.raw_glyph_metrics(font_key, glyph_index).unwrap();
+
+        let scale = rasterizer.scale_for(font_key, size).unwrap();
+        assert!((raw.advance_width as f64 * scale - f64::from(bounds.advance)).abs() < 0.01);
+    }
+
+    #[test]
+    fn disabled_fallback() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_fallback_enabled(false);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: '\u{1F680}', font_key, size });
+
+        assert!(matches!(glyph, Err(Error::MissingGlyph(_))));
+    }
+
+    #[test]
+    fn zwj_sequence_is_single_glyph() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let cluster = rasterizer.get_cluster(family, font_key, size).unwrap();
+        let man = rasterizer.get_cluster("\u{1F468}", font_key, size).unwrap();
+
+        assert!(matches!(cluster.buffer, BitmapBuffer::Rgba(_)));
+        assert_eq!(cluster.advance, man.advance);
+    }
+
+    #[test]
+    fn enhanced_contrast_keeps_bounds() {
+        let mut coverage = [0, 128, 255];
+        enhance_contrast(&mut coverage, 1.);
+        assert_eq!(coverage, [0, 170, 255]);
+
+        let mut coverage = [0, 128, 255];
+        enhance_contrast(&mut coverage, 0.);
+        assert_eq!(coverage, [0, 128, 255]);
+    }
+
+    #[test]
+    fn linear_coverage_transfer() {
+        let mut coverage = [0, 128, 255];
+        linearize_coverage(&mut coverage, 1.8);
+        assert_eq!(coverage, [0, 74, 255]);
+
+        let mut coverage = [0, 128, 255];
+        linearize_coverage(&mut coverage, 1.);
+        assert_eq!(coverage, [0, 128, 255]);
+    }
+
+    #[test]
+    fn replacement_char_fallback() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        // Unassigned codepoint which isn't covered by any font.
+        let glyph = GlyphKey { character: '\u{10FFFD}', font_key, size };
+        let replacement = GlyphKey { character: char::REPLACEMENT_CHARACTER, font_key, size };
+
+        let missing = match rasterizer.get_glyph(glyph) {
+            Err(Error::MissingGlyph(missing)) => missing,
+            _ => panic!("expected missing glyph"),
+        };
+        let replacement = rasterizer.get_glyph(replacement).unwrap();
+
+        assert_eq!(missing.character, '\u{10FFFD}');
+        assert_eq!((missing.width, missing.height), (replacement.width, replacement.height));
+    }
+
+    #[test]
+    fn advance_rounding() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(9.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph = GlyphKey { character: 'a', font_key, size };
+
+        rasterizer.set_advance_rounding(AdvanceRounding::Floor);
+        let floor = rasterizer.get_glyph(glyph).unwrap().advance.0;
+        rasterizer.set_advance_rounding(AdvanceRounding::Ceil);
+        let ceil = rasterizer.get_glyph(glyph).unwrap().advance.0;
+
+        assert_eq!(floor + 1, ceil);
+    }
+
+    #[test]
+    fn load_first_available_family() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let style = Style::Description { slant: Slant::Normal, weight: Weight::Normal };
+        let size = Size::new(12.);
+        let names = ["Nonexistent Family".to_owned(), "Consolas".to_owned()];
+
+        let font_key = rasterizer.load_first_available(&names, style.clone(), size).unwrap();
+        assert_eq!(rasterizer.get_loaded_font(font_key).unwrap().family_name, "Consolas");
+
+        let names = ["Nonexistent Family".to_owned(), "Missing Family".to_owned()];
+        match rasterizer.load_first_available(&names, style, size) {
+            Err(Error::FontNotFound(desc)) => {
+                assert_eq!(desc.name, "Nonexistent Family, Missing Family")
+            },
+            _ => panic!("expected font not found"),
+        }
+    }
+
+    #[test]
+    fn stats_count_stages() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph = GlyphKey { character: 'a', font_key, size };
+
+        rasterizer.get_glyph(glyph).unwrap();
+        assert_eq!(rasterizer.take_stats(), RasterStats::default());
+
+        rasterizer.enable_stats(true);
+        rasterizer.get_glyph(glyph).unwrap();
+        let stats = rasterizer.take_stats();
+
+        assert_eq!(stats.glyph_index_lookups.count, 1);
+        assert_eq!(stats.fallback_lookups.count, 0);
+        assert_eq!(stats.glyph_run_analyses.count, 1);
+        assert_eq!(stats.texture_copies.count, 1);
+        assert_eq!(rasterizer.take_stats(), RasterStats::default());
+    }
+
+    #[test]
+    fn display_params_subpixel_order() {
+        let mut coverage = [255, 128, 0];
+        let params = DisplayParams {
+            pixel_geometry: dwrite::DWRITE_PIXEL_GEOMETRY_BGR,
+            ..Default::default()
+        };
+        params.apply(&mut coverage);
+        assert_eq!(coverage, [0, 128, 255]);
+
+        let mut coverage = [255, 128, 0];
+        let params = DisplayParams { cleartype_level: 0., ..Default::default() };
+        params.apply(&mut coverage);
+        assert_eq!(coverage, [128, 128, 128]);
+    }
+
+    #[test]
+    fn resolved_style_of_substituted_weight() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = rasterizer.load_font(&regular("Arial Black"), size).unwrap();
+
+        // Arial Black has no regular weight face, so its black face is used instead.
+        let resolved = rasterizer.resolved_style(font_key).unwrap();
+
+        assert_eq!(resolved.weight, Weight::Bold);
+        assert_eq!(resolved.weight_value, 900);
+        assert_eq!(resolved.slant, Slant::Normal);
+        assert_eq!(resolved.stretch, 5);
+    }
+
+    #[test]
+    fn supplementary_plane_glyph_index() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = rasterizer.load_font(&regular("Cambria Math"), size).unwrap();
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+
+        assert_ne!(rasterizer.get_glyph_index(face, '\u{1d400}'), MISSING_GLYPH_INDEX);
+        assert_ne!(rasterizer.get_glyph_index(face, 'A'), MISSING_GLYPH_INDEX);
+    }
+
+    #[test]
+    fn font_desc_builder() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let desc = FontDesc::builder().name("Consolas").weight(Weight::Bold).build();
+        let font_key = rasterizer.load_font(&desc, size).unwrap();
+
+        let style = Style::Description { slant: Slant::Normal, weight: Weight::Bold };
+        assert_eq!(desc, FontDesc::new("Consolas", style));
+        assert_eq!(rasterizer.resolved_style(font_key).unwrap().weight_value, 700);
+    }
+
+    #[test]
+    fn load_font_by_full_name() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+
+        let font_key = rasterizer.load_font_by_full_name("consolas bold").unwrap();
+        let resolved = rasterizer.resolved_style(font_key).unwrap();
+        assert_eq!(resolved.weight_value, 700);
+
+        // Loading the same font again reuses its key.
+        assert_eq!(rasterizer.load_font_by_full_name("Consolas Bold").unwrap(), font_key);
+        assert_eq!(rasterizer.loaded_keys().count(), 1);
+
+        let result = rasterizer.load_font_by_full_name("Consolas Nonexistent");
+        assert!(matches!(result, Err(Error::FontNameNotFound(_))));
+    }
+
+    #[test]
+    fn supported_scripts() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = rasterizer.load_font(&regular("Segoe UI"), size).unwrap();
+
+        let scripts = rasterizer.supported_scripts(font_key).unwrap();
+        assert!(scripts.contains(b"latn"));
+        assert!(scripts.contains(b"arab"));
+    }
+
+    #[test]
+    fn auto_rendering_mode() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph_key = GlyphKey { character: 'a', font_key, size };
+        let is_grayscale = |glyph: RasterizedGlyph| match glyph.buffer {
+            BitmapBuffer::Rgb(buffer) => {
+                buffer.chunks(3).all(|rgb| rgb[0] == rgb[1] && rgb[1] == rgb[2])
+            },
+            _ => panic!("expected RGB glyph"),
+        };
+
+        rasterizer.set_rendering_mode(crate::RenderingMode::Auto);
+        assert!(is_grayscale(rasterizer.get_glyph(glyph_key).unwrap()));
+
+        rasterizer.set_background_is_opaque(true);
+        assert!(!is_grayscale(rasterizer.get_glyph(glyph_key).unwrap()));
+
+        rasterizer.set_rendering_mode(crate::RenderingMode::Subpixel);
+        rasterizer.set_background_is_opaque(false);
+        assert!(is_grayscale(rasterizer.get_glyph(glyph_key).unwrap()));
+    }
+
+    #[test]
+    fn batched_advances() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = rasterizer.load_font(&regular("Arial"), size).unwrap();
+
+        let advances = rasterizer.advances(font_key, &['i', 'W', '\u{10FFFD}'], size).unwrap();
+        let notdef = rasterizer.raw_glyph_metrics(font_key, MISSING_GLYPH_INDEX).unwrap();
+        let scale = rasterizer.scale_for(font_key, size).unwrap();
+
+        assert_eq!(advances.len(), 3);
+        assert!(advances[0] < advances[1]);
+        assert_eq!(advances[2], (f64::from(notdef.advance_width) * scale).round() as f32);
+
+        // Advances match the ones of rasterized glyphs.
+        let glyph = rasterizer.get_glyph(GlyphKey { font_key, character: 'W', size }).unwrap();
+        assert_eq!(advances[1], glyph.advance.0 as f32);
+
+        rasterizer.set_advance_rounding(AdvanceRounding::None);
+        let advances = rasterizer.advances(font_key, &['\u{10FFFD}'], size).unwrap();
+        assert_eq!(advances[0], (f64::from(notdef.advance_width) * scale) as f32);
+    }
+
+    #[test]
+    fn batched_glyphs() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_fallback_enabled(false);
+        rasterizer.set_replacement_char_fallback(false);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let missing = GlyphKey { font_key, character: '\u{10FFFD}', size };
+        let keys = [GlyphKey { font_key, character: 'a', size }, missing];
+        let glyphs = rasterizer.get_glyphs(&keys).unwrap();
+
+        let expected = rasterizer.get_glyph(keys[0]).unwrap();
+        assert_eq!(glyphs[0].as_ref().unwrap().pixels(), expected.pixels());
+        assert!(matches!(glyphs[1], Err(Error::MissingGlyph(_))));
+    }
+
+    #[test]
+    fn notdef_glyph() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_missing_glyph_policy(MissingGlyphPolicy::Blank);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_notdef_glyph(font_key, size).unwrap();
+
+        assert!(glyph.width > 0 && glyph.height > 0);
+    }
+
+    #[test]
+    fn matching_faces() {
+        let rasterizer = DirectWriteRasterizer::new().unwrap();
+
+        let faces = rasterizer.matching_faces("Consolas").unwrap();
+        let bold = faces.iter().find(|face| face.name == "Bold").unwrap();
+        assert_eq!(bold.style.weight, Weight::Bold);
+        assert_eq!(bold.style.slant, Slant::Normal);
+
+        let result = rasterizer.matching_faces("Nonexistent Family");
+        assert!(matches!(result, Err(Error::FontNameNotFound(_))));
+    }
+
+    #[test]
+    fn line_metrics_source() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = rasterizer.load_font(&regular("Segoe UI"), size).unwrap();
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+        let scale = design_unit_scale(face.metrics().metrics0().designUnitsPerEm, size);
+        let os2 = face.font_table(tables::OS2).unwrap().unwrap();
+        let typo = tables::typo_line_metrics(&os2).unwrap();
+
+        rasterizer.set_line_metrics_source(LineMetricsSource::TypoOs2);
+        let metrics = rasterizer.metrics(font_key, size).unwrap();
+
+        assert_eq!(metrics.ascent, (f64::from(typo.ascent) * scale) as f32);
+        assert_eq!(metrics.line_gap, (f64::from(typo.line_gap) * scale) as f32);
+    }
+
+    #[test]
+    fn has_outlines() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let font_key = consolas(&mut rasterizer, Size::new(12.));
+
+        assert!(rasterizer.has_outlines(font_key).unwrap());
+    }
+
+    #[test]
+    fn integer_pixel_sizes() {
+        for px in 2..=1024 {
+            assert_eq!(Size::from_px(px as f32).as_px(), px as f32);
+        }
+
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::from_px(13.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph_px(font_key, 'a', 13).unwrap();
+        let expected = rasterizer.get_glyph(GlyphKey { character: 'a', font_key, size }).unwrap();
+        assert_eq!((glyph.width, glyph.height), (expected.width, expected.height));
+    }
+
+    #[test]
+    fn setting_getters() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        assert_eq!(rasterizer.rendering_mode(), crate::RenderingMode::Grayscale);
+        assert!(!rasterizer.grid_fitting());
+        assert_eq!(rasterizer.background_is_opaque(), None);
+
+        rasterizer.set_rendering_mode(crate::RenderingMode::Subpixel);
+        rasterizer.set_grid_fitting(true);
+        rasterizer.set_stem_darkening(true, 0.5);
+
+        assert_eq!(rasterizer.rendering_mode(), crate::RenderingMode::Subpixel);
+        assert!(rasterizer.grid_fitting());
+        assert_eq!(rasterizer.stem_darkening(), Some(0.5));
+    }
+
+    #[test]
+    fn fallible_font_conversion() {
+        let rasterizer = DirectWriteRasterizer::new().unwrap();
+        let family = rasterizer.find_family("Consolas").unwrap();
+
+        let font = Font::try_from(family.font(0).unwrap()).unwrap();
+
+        assert_eq!(font.family_name, "Consolas");
+        assert_ne!(font.face.get_glyph_count(), 0);
+    }
+
+    #[test]
+    fn antialias_mode_override() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph_key = GlyphKey { character: 'a', font_key, size };
+        let buffer = |glyph: RasterizedGlyph| match glyph.buffer {
+            BitmapBuffer::Rgb(buffer) => buffer,
+            _ => panic!("expected RGB glyph"),
+        };
+
+        rasterizer.set_antialias_mode(AntialiasMode::ClearType);
+        let subpixel = buffer(rasterizer.get_glyph(glyph_key).unwrap());
+        assert!(subpixel.chunks(3).any(|rgb| rgb[0] != rgb[1] || rgb[1] != rgb[2]));
+
+        rasterizer.set_antialias_mode(AntialiasMode::None);
+        let aliased = buffer(rasterizer.get_glyph(glyph_key).unwrap());
+        assert!(aliased.iter().all(|&value| value == 0x00 || value == 0xFF));
+    }
+
+    #[test]
+    fn font_key_for_loaded_desc() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let desc = regular("Consolas");
+        assert_eq!(rasterizer.font_key_for(&desc), None);
+
+        let font_key = rasterizer.load_font(&desc, Size::new(12.)).unwrap();
+
+        assert_eq!(rasterizer.font_key_for(&desc), Some(font_key));
+    }
+
+    #[test]
+    fn register_font_face() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let family = rasterizer.find_family("Consolas").unwrap();
+        let font = family.font(0).unwrap();
+
+        let font_key = rasterizer.register_font_face(font).unwrap();
+        let size = Size::new(12.);
+        let glyph = rasterizer.get_glyph(GlyphKey { character: 'a', font_key, size }).unwrap();
+
+        assert!(glyph.width > 0);
+    }
+
+    #[test]
+    fn glyph_padding() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph_key = GlyphKey { character: 'a', font_key, size };
+
+        let glyph = rasterizer.get_glyph(glyph_key).unwrap();
+        rasterizer.set_glyph_padding(2);
+        let padded = rasterizer.get_glyph(glyph_key).unwrap();
+
+        assert_eq!((padded.width, padded.height), (glyph.width + 4, glyph.height + 4));
+        assert_eq!((padded.left, padded.top), (glyph.left - 2, glyph.top + 2));
+        assert_eq!(padded.advance, glyph.advance);
+
+        let padded_buffer = match padded.buffer {
+            BitmapBuffer::Rgb(buffer) => buffer,
+            _ => panic!("expected RGB glyph"),
+        };
+        let stride = padded.width as usize * 3;
+        assert!(padded_buffer[..stride * 2].iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn fallback_font_is_cached() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph = GlyphKey { character: '漢', font_key, size };
+
+        rasterizer.enable_stats(true);
+        let first = rasterizer.get_glyph(glyph).unwrap();
+        let second = rasterizer.get_glyph(glyph).unwrap();
+        assert_eq!((first.width, first.height), (second.width, second.height));
+        assert_eq!(rasterizer.take_stats().fallback_lookups.count, 1);
+
+        rasterizer.refresh_system_fonts();
+        rasterizer.get_glyph(glyph).unwrap();
+        assert_eq!(rasterizer.take_stats().fallback_lookups.count, 1);
+    }
+
+    #[test]
+    fn font_info() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let font_key = consolas(&mut rasterizer, Size::new(12.));
+
+        let info = rasterizer.font_info(font_key).unwrap();
+        assert_eq!(info.family_name.as_deref(), Some("Consolas"));
+        assert_eq!(info.full_name.as_deref(), Some("Consolas"));
+        assert!(info.copyright.is_some_and(|copyright| copyright.contains("Microsoft")));
+        assert!(info.version.is_some());
+    }
+
+    #[test]
+    fn grid_fit_threshold() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_grid_fit_threshold(Some(16.));
+
+        let settings = rasterizer.rasterization_settings().unwrap();
+        assert_eq!(settings.grid_fit_mode(12.), DWRITE_GRID_FIT_MODE_ENABLED);
+        assert_eq!(settings.grid_fit_mode(16.), DWRITE_GRID_FIT_MODE_ENABLED);
+        assert_eq!(settings.grid_fit_mode(24.), DWRITE_GRID_FIT_MODE_DISABLED);
+
+        rasterizer.set_grid_fit_threshold(None);
+        let settings = rasterizer.rasterization_settings().unwrap();
+        assert_eq!(settings.grid_fit_mode(12.), DWRITE_GRID_FIT_MODE_DISABLED);
+    }
+
+    #[test]
+    fn named_instances() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let font_key = consolas(&mut rasterizer, size);
+        assert!(rasterizer.named_instances(font_key).unwrap().is_empty());
+
+        let font_key = rasterizer.load_font(&regular("Bahnschrift"), size).unwrap();
+        let instances = rasterizer.named_instances(font_key).unwrap();
+        let bold = instances.iter().find(|instance| instance.name == "Bold").unwrap();
+        let weight = bold.coordinates.iter().find(|variation| &variation.tag == b"wght").unwrap();
+        assert_eq!(weight.value, 700.);
+    }
+
+    #[test]
+    fn stroked_glyph() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(24.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+        let glyph_index = rasterizer.get_glyph_index(face, 'o');
+        let filled = rasterizer.get_glyph_sdf(GlyphKey { character: 'o', font_key, size }, 1.);
+        let filled = filled.unwrap();
+
+        let stroked = rasterizer.get_glyph_stroked(font_key, glyph_index, size, 4.).unwrap();
+        assert!(matches!(stroked.buffer, BitmapBuffer::Alpha(_)));
+        assert!(stroked.left < filled.left && stroked.top > filled.top);
+        assert_eq!(stroked.advance, filled.advance);
+
+        let (left, right) = rasterizer.glyph_side_bearings(font_key, glyph_index, size).unwrap();
+        assert_eq!(stroked.side_bearings, (left - 2., right - 2.));
+
+        rasterizer.fonts.get_mut(&font_key).unwrap().has_outlines = false;
+        let result = rasterizer.get_glyph_stroked(font_key, glyph_index, size, 4.);
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn notdef_index_override() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_fallback_enabled(false);
+        rasterizer.set_replacement_char_fallback(false);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let face = &rasterizer.get_loaded_font(font_key).unwrap().face;
+        let glyph_index = rasterizer.get_glyph_index(face, 'A');
+        let expected = rasterizer.get_glyph(GlyphKey { character: 'A', font_key, size }).unwrap();
+
+        rasterizer.set_notdef_index(glyph_index);
+        assert_eq!(rasterizer.notdef_index(), glyph_index);
+
+        let notdef = rasterizer.get_notdef_glyph(font_key, size).unwrap();
+        assert_eq!((notdef.width, notdef.height), (expected.width, expected.height));
+
+        let missing = GlyphKey { character: '\u{10FFFD}', font_key, size };
+        match rasterizer.get_glyph(missing) {
+            Err(Error::MissingGlyph(glyph)) => {
+                assert_eq!((glyph.width, glyph.height), (expected.width, expected.height))
+            },
+            _ => panic!("expected missing glyph"),
+        }
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: 'A', font_key, size });
+        assert!(matches!(glyph, Err(Error::MissingGlyph(_))));
+    }
+
+    #[test]
+    fn color_font_detection() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let font_key = consolas(&mut rasterizer, size);
+        assert!(!rasterizer.get_loaded_font(font_key).unwrap().is_color);
+
+        let font_key = rasterizer.load_font(&regular("Segoe UI Emoji"), size);
+        assert!(rasterizer.get_loaded_font(font_key.unwrap()).unwrap().is_color);
+    }
+
+    #[test]
+    fn pixel_geometry_override() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Subpixel);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph_key = GlyphKey { character: 'a', font_key, size };
+
+        let rgb = rasterizer.get_glyph(glyph_key).unwrap();
+        rasterizer.set_pixel_geometry(PixelGeometry::Bgr);
+        let bgr = rasterizer.get_glyph(glyph_key).unwrap();
+
+        let (BitmapBuffer::Rgb(rgb), BitmapBuffer::Rgb(bgr)) = (rgb.buffer, bgr.buffer) else {
+            panic!("expected RGB glyphs");
+        };
+        for (rgb, bgr) in rgb.chunks_exact(3).zip(bgr.chunks_exact(3)) {
+            assert_eq!(rgb, [bgr[2], bgr[1], bgr[0]]);
+        }
+
+        rasterizer.set_pixel_geometry(PixelGeometry::Flat);
+        let flat = rasterizer.get_glyph(glyph_key).unwrap();
+        let BitmapBuffer::Rgb(flat) = flat.buffer else { panic!("expected RGB glyph") };
+        assert!(flat.chunks_exact(3).all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+    }
+
+    #[test]
+    fn current_locale() {
+        let locale = get_current_locale();
+        assert!(!locale.is_empty() && !locale.contains('\0'));
+    }
+
+    #[test]
+    fn mono_output() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Aliased);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph_key = GlyphKey { character: 'a', font_key, size };
+
+        let glyph = rasterizer.get_glyph(glyph_key).unwrap();
+        rasterizer.set_mono_output(true);
+        let mono = rasterizer.get_glyph(glyph_key).unwrap();
+
+        assert_eq!(mono.format(), crate::PixelFormat::Mono1);
+        assert_eq!((mono.width, mono.height), (glyph.width, glyph.height));
+        assert_eq!(mono.stride(), (glyph.width as usize).div_ceil(8));
+
+        let BitmapBuffer::Bitmap1 { data, stride_bits } = &mono.buffer else { unreachable!() };
+        for (index, pixel) in glyph.pixels().chunks_exact(3).enumerate() {
+            let (row, column) = (index / glyph.width as usize, index % glyph.width as usize);
+            let bit = row * stride_bits + column;
+            assert_eq!(data[bit / 8] & (0x80 >> (bit % 8)) != 0, pixel[0] == 0xFF);
+        }
+
+        // Strokes keep their alpha buffer.
+        let glyph_index = rasterizer.glyph_for_char(font_key, 'a').unwrap().unwrap();
+        let stroked = rasterizer.get_glyph_stroked(font_key, glyph_index, size, 1.).unwrap();
+        assert_eq!(stroked.format(), crate::PixelFormat::A8);
+
+        rasterizer.set_rendering_mode(crate::RenderingMode::Grayscale);
+        let glyph = rasterizer.get_glyph(glyph_key).unwrap();
+        assert_eq!(glyph.format(), crate::PixelFormat::Rgb8);
+    }
+
+    #[test]
+    fn resolve_font_for() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let font_key = consolas(&mut rasterizer, Size::new(12.));
+
+        let resolved = rasterizer.resolve_font_for(font_key, "abc").unwrap();
+        assert_eq!(resolved.family_name, "Consolas");
+        assert!(!resolved.is_fallback && resolved.has_glyph);
+
+        let resolved = rasterizer.resolve_font_for(font_key, "漢字").unwrap();
+        assert_ne!(resolved.family_name, "Consolas");
+        assert!(resolved.is_fallback && resolved.has_glyph);
+    }
+
+    #[test]
+    fn synthetic_smallcaps() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(24.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let lowercase = rasterizer.get_glyph(GlyphKey { character: 'h', font_key, size }).unwrap();
+        let uppercase = rasterizer.get_glyph(GlyphKey { character: 'H', font_key, size }).unwrap();
+
+        rasterizer.set_synthetic_smallcaps(true, 0.5);
+        let smallcaps = rasterizer.get_glyph(GlyphKey { character: 'h', font_key, size }).unwrap();
+        assert!(smallcaps.height < uppercase.height && smallcaps.height < lowercase.height);
+        assert_eq!(smallcaps.advance, lowercase.advance);
+
+        // Characters without uppercase letter are not affected.
+        let digit = rasterizer.get_glyph(GlyphKey { character: '1', font_key, size }).unwrap();
+        assert!(digit.height > smallcaps.height);
+    }
+
+    #[test]
+    fn glyph_for_char() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph_index = rasterizer.glyph_for_char(font_key, 'a').unwrap();
+        let shaped = rasterizer.shape_and_rasterize("a", font_key, size).unwrap();
+        assert_eq!(glyph_index, Some(shaped[0].glyph_index));
+
+        assert_eq!(rasterizer.glyph_for_char(font_key, '漢').unwrap(), None);
+    }
+
+    #[test]
+    fn clear() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Subpixel);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        rasterizer.clear();
+
+        let glyph = rasterizer.get_glyph(GlyphKey { character: 'a', font_key, size });
+        assert!(matches!(glyph, Err(Error::UnknownFontKey)));
+        assert!(matches!(rasterizer.metrics(font_key, size), Err(Error::UnknownFontKey)));
+        assert_eq!(rasterizer.rendering_mode(), crate::RenderingMode::Subpixel);
+
+        let new_key = consolas(&mut rasterizer, size);
+        assert_ne!(new_key, font_key);
+    }
+
+    #[test]
+    fn real_faces_for_weight_and_slant_combinations() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let mut faces = Vec::new();
+        for weight in [Weight::Normal, Weight::Bold] {
+            for slant in [Slant::Normal, Slant::Italic] {
+                let style = Style::Description { slant, weight };
+                let desc = FontDesc::new("Consolas", style);
+                let font_key = rasterizer.load_font(&desc, size).unwrap();
+
+                let font = rasterizer.get_loaded_font(font_key).unwrap();
+                assert_eq!(font.face.simulations(), FontSimulations::None);
+
+                let resolved = rasterizer.resolved_style(font_key).unwrap();
+                assert_eq!((resolved.weight, resolved.slant), (weight, slant));
+
+                faces.push(rasterizer.font_path(font_key).unwrap().unwrap());
+            }
+        }
+
+        // Every combination has its own face in the family.
+        faces.sort();
+        faces.dedup();
+        assert_eq!(faces.len(), 4);
+    }
+
+    #[test]
+    fn loaded_keys_and_fonts() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let font_key = consolas(&mut rasterizer, Size::new(12.));
+
+        assert_eq!(rasterizer.loaded_keys().collect::<Vec<_>>(), [font_key]);
+        assert_eq!(rasterizer.loaded_fonts().collect::<Vec<_>>(), [(font_key, "Consolas")]);
+    }
+
+    #[test]
+    fn invalid_size() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let font_key = consolas(&mut rasterizer, Size::new(12.));
+
+        // Non-finite sizes are the only ones which aren't clamped to a positive size.
+        let size = Size::new(f32::NAN);
+        assert_eq!(size.as_px(), 0.);
+        assert!(matches!(rasterizer.metrics(font_key, size), Err(Error::InvalidSize(_))));
+
+        let glyph = GlyphKey { font_key, character: 'a', size };
+        assert!(matches!(rasterizer.get_glyph(glyph), Err(Error::InvalidSize(_))));
+        assert!(matches!(rasterizer.get_glyph_sdf(glyph, 4.), Err(Error::InvalidSize(_))));
+
+        let glyph_index = rasterizer.glyph_for_char(font_key, 'a').unwrap().unwrap();
+        let outline = rasterizer.glyph_outline(font_key, glyph_index, size);
+        assert!(matches!(outline, Err(Error::InvalidSize(_))));
+        let stroked = rasterizer.get_glyph_stroked(font_key, glyph_index, size, 1.);
+        assert!(matches!(stroked, Err(Error::InvalidSize(_))));
+
+        let shaped = rasterizer.shape_and_rasterize("a", font_key, size);
+        assert!(matches!(shaped, Err(Error::InvalidSize(_))));
+        let vertical_metrics = rasterizer.vertical_metrics(font_key, size);
+        assert!(matches!(vertical_metrics, Err(Error::InvalidSize(_))));
+    }
+
+    #[test]
+    fn last_resort_font() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let desc = regular("Segoe UI Symbol");
+        let symbol_key = rasterizer.load_font(&desc, size).unwrap();
+        let path = rasterizer.font_path(symbol_key).unwrap().unwrap();
+
+        let font_key = consolas(&mut rasterizer, size);
+        rasterizer.set_fallback_enabled(false);
+        assert!(!rasterizer.resolve_font_for(font_key, "\u{2603}").unwrap().has_glyph);
+
+        rasterizer.set_last_resort_font(Some(std::fs::read(path).unwrap())).unwrap();
+        assert_eq!(rasterizer.last_resort_font(), Some("Segoe UI Symbol"));
+
+        let resolved = rasterizer.resolve_font_for(font_key, "\u{2603}").unwrap();
+        assert_eq!(resolved.family_name, "Segoe UI Symbol");
+        assert!(resolved.is_fallback && resolved.has_glyph);
+
+        let glyph = GlyphKey { font_key, character: '\u{2603}', size };
+        assert!(rasterizer.get_glyph(glyph).is_ok());
+
+        assert!(rasterizer.set_last_resort_font(Some(vec![0; 16])).is_err());
+        rasterizer.set_last_resort_font(None).unwrap();
+        assert_eq!(rasterizer.last_resort_font(), None);
+    }
+
+    #[test]
+    fn glyph_side_bearings_match_rasterized_glyph() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let style = Style::Description { slant: Slant::Italic, weight: Weight::Normal };
+        let size = Size::new(12.);
+        let font_key = rasterizer.load_font(&FontDesc::new("Consolas", style), size).unwrap();
+
+        let glyph = rasterizer.get_glyph(GlyphKey { font_key, character: 'f', size }).unwrap();
+        let glyph_index = rasterizer.glyph_for_char(font_key, 'f').unwrap().unwrap();
+        let bearings = rasterizer.glyph_side_bearings(font_key, glyph_index, size).unwrap();
+
+        assert_eq!(glyph.side_bearings, bearings);
+    }
+
+    #[test]
+    fn fonts_from_dir() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let font_key = consolas(&mut rasterizer, size);
+        let system_path = rasterizer.font_path(font_key).unwrap().unwrap();
+
+        let dir = std::env::temp_dir().join("crossfont-fonts-from-dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(&system_path, dir.join("consola.ttf")).unwrap();
+        fs::write(dir.join("invalid.ttf"), [0; 16]).unwrap();
+        fs::write(dir.join("readme.txt"), "not a font").unwrap();
+
+        let families = rasterizer.add_fonts_from_dir(&dir).unwrap();
+        assert_eq!(families, ["Consolas"]);
+
+        // Fonts from the directory take precedence over the installed fonts.
+        rasterizer.clear();
+        let font_key = consolas(&mut rasterizer, size);
+        let path = rasterizer.font_path(font_key).unwrap().unwrap();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+
+        drop(rasterizer);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn normalized_family_name_from_dir() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+
+        let desc = regular("Segoe UI Symbol");
+        let font_key = rasterizer.load_font(&desc, size).unwrap();
+        let system_path = rasterizer.font_path(font_key).unwrap().unwrap();
+
+        let dir = std::env::temp_dir().join("crossfont-normalized-family-from-dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(&system_path, dir.join("seguisym.ttf")).unwrap();
+        rasterizer.add_fonts_from_dir(&dir).unwrap();
+
+        rasterizer.clear();
+        let font_key = rasterizer.load_font(&regular("segoeuisymbol"), size).unwrap();
+        let path = rasterizer.font_path(font_key).unwrap().unwrap();
+        assert_eq!(path.parent(), Some(dir.as_path()));
+
+        drop(rasterizer);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cap_center_offset() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let metrics = rasterizer.metrics(font_key, size).unwrap();
+        let offset = rasterizer.cap_center_offset(font_key, size).unwrap();
+
+        // Capitals end above the center of the line, but below its top.
+        let line_height = metrics.line_height as f32;
+        assert!(offset > line_height / 2. && offset < line_height);
+    }
+
+    #[test]
+    fn rendering_mode_option() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Grayscale);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph = GlyphKey { font_key, character: 'a', size };
+
+        let is_aliased = |glyph: &RasterizedGlyph| {
+            glyph.pixels().iter().all(|&value| value == 0 || value == 0xFF)
+        };
+
+        let antialiased = rasterizer.get_glyph(glyph).unwrap();
+        assert!(!is_aliased(&antialiased));
+
+        let rendering_mode = Some(crate::RenderingMode::Aliased);
+        let options = RasterizeOptions { rendering_mode, ..Default::default() };
+        let aliased = rasterizer.get_glyph_with(glyph, options).unwrap();
+        assert!(is_aliased(&aliased));
+
+        // The global rendering mode is unaffected.
+        let antialiased = rasterizer.get_glyph(glyph).unwrap();
+        assert!(!is_aliased(&antialiased));
+    }
+
+    #[test]
+    fn rendering_mode_option_with_mono_output() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Aliased);
+        rasterizer.set_mono_output(true);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+        let glyph = GlyphKey { font_key, character: 'a', size };
+
+        assert_eq!(rasterizer.get_glyph(glyph).unwrap().format(), crate::PixelFormat::Mono1);
+
+        // Antialiased overrides keep their partial coverage.
+        let rendering_mode = Some(crate::RenderingMode::Grayscale);
+        let options = RasterizeOptions { rendering_mode, ..Default::default() };
+        let antialiased = rasterizer.get_glyph_with(glyph, options).unwrap();
+        assert_eq!(antialiased.format(), crate::PixelFormat::Rgb8);
+        assert!(antialiased.pixels().iter().any(|&value| value != 0 && value != 0xFF));
+    }
+
+    #[test]
+    fn line_height_matches_line_metrics() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        for source in [LineMetricsSource::Hhea, LineMetricsSource::TypoOs2] {
+            rasterizer.set_line_metrics_source(source);
+            let metrics = rasterizer.metrics(font_key, size).unwrap();
+
+            assert!(metrics.descent < 0.);
+            let line_height = metrics.ascent - metrics.descent + metrics.line_gap;
+            assert!((metrics.line_height as f32 - line_height).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn cleartype_antialiasing_with_aliased_rendering() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        rasterizer.set_rendering_mode(crate::RenderingMode::Aliased);
+        rasterizer.set_antialias_mode(AntialiasMode::ClearType);
+        let size = Size::new(12.);
+        let font_key = consolas(&mut rasterizer, size);
+
+        let glyph = rasterizer.get_glyph(GlyphKey { font_key, character: 'a', size }).unwrap();
+
+        assert!(glyph.width > 0 && glyph.height > 0);
+        assert!(glyph.pixels().iter().any(|&value| value != 0));
+    }
+
+    #[test]
+    fn face_index_within_file_of_matched_style() {
+        let mut rasterizer = DirectWriteRasterizer::new().unwrap();
+        let size = Size::new(12.);
+        let style = Style::Description { slant: Slant::Normal, weight: Weight::Bold };
+
+        let desc = FontDesc::new("Consolas", style);
+        let bold_key = rasterizer.load_font(&desc, size).unwrap();
+        let indexed_key = rasterizer.load_font(&desc.clone().with_face_index(0), size).unwrap();
+
+        // Consolas Regular also has face index 0, but lives in a different file.
+        let bold_path = rasterizer.font_path(bold_key).unwrap();
+        assert_eq!(rasterizer.font_path(indexed_key).unwrap(), bold_path);
+        assert_eq!(rasterizer.resolved_style(indexed_key).unwrap().weight, Weight::Bold);
+    }
+}
@@ -0,0 +1,295 @@
+//! Glyph outline extraction and distance field generation.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use dwrote::{FontFace, OutlineBuilder};
+
+use super::{tables, FontTable};
+use crate::{BitmapBuffer, Error, PathCommand, RasterizedGlyph};
+
+/// Maximum number of line segments a single curve is flattened into.
+const MAX_CURVE_SEGMENTS: f32 = 32.;
+
+/// Point in pixel coordinates, with the Y axis pointing down.
+type Point = (f32, f32);
+
+/// Closed polygons approximating a glyph's outline.
+pub type Contours = Vec<Vec<Point>>;
+
+/// Check if a font has glyph outlines in a `glyf`, `CFF ` or `CFF2` table.
+///
+/// Fonts without any of these tables only have embedded bitmaps.
+pub fn has_outlines(face: &FontFace) -> Result<bool, Error> {
+    for tag in [tables::GLYF, tables::CFF, tables::CFF2] {
+        if FontTable::exists(face, tag)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Get the outline of a glyph at the given size in pixels.
+pub fn glyph_path(
+    face: &FontFace,
+    em_size: f32,
+    glyph_index: u16,
+) -> Result<Vec<PathCommand>, Error> {
+    let commands = Rc::new(RefCell::new(Vec::new()));
+    let recorder = PathRecorder { commands: commands.clone() };
+
+    face.glyph_run_outline(em_size, &[glyph_index], None, None, false, false, Box::new(recorder))
+        .map_err(|err| Error::PlatformError(format!("unable to get glyph outline: {err}")))?;
+
+    let commands = commands.borrow_mut().split_off(0);
+    Ok(commands)
+}
+
+/// Approximate an outline with polygons.
+pub fn flatten(path: &[PathCommand]) -> Contours {
+    let mut contours: Contours = Vec::new();
+    let mut current = (0., 0.);
+
+    for command in path {
+        match *command {
+            PathCommand::MoveTo { x, y } => contours.push(vec![(x, y)]),
+            PathCommand::LineTo { x, y } => {
+                if let Some(contour) = contours.last_mut() {
+                    contour.push((x, y));
+                }
+            },
+            PathCommand::QuadTo { cx, cy, x, y } => {
+                // Elevate to a cubic curve with the same shape.
+                let (x0, y0) = current;
+                let c0 = (x0 + 2. / 3. * (cx - x0), y0 + 2. / 3. * (cy - y0));
+                let c1 = (x + 2. / 3. * (cx - x), y + 2. / 3. * (cy - y));
+                if let Some(contour) = contours.last_mut() {
+                    flatten_cubic(contour, current, c0, c1, (x, y));
+                }
+            },
+            PathCommand::CubicTo { c0x, c0y, c1x, c1y, x, y } => {
+                if let Some(contour) = contours.last_mut() {
+                    flatten_cubic(contour, current, (c0x, c0y), (c1x, c1y), (x, y));
+                }
+            },
+            PathCommand::Close => continue,
+        }
+
+        current = contours.last().and_then(|contour| contour.last().copied()).unwrap_or_default();
+    }
+
+    contours
+}
+
+/// Approximate a cubic Bézier curve with line segments.
+fn flatten_cubic(contour: &mut Vec<Point>, start: Point, c0: Point, c1: Point, end: Point) {
+    let control_length = distance(start, c0) + distance(c0, c1) + distance(c1, end);
+    let segments = (control_length / 2.).ceil().clamp(1., MAX_CURVE_SEGMENTS) as usize;
+
+    for segment in 1..=segments {
+        let t = segment as f32 / segments as f32;
+        let mt = 1. - t;
+        let a = mt * mt * mt;
+        let b = 3. * mt * mt * t;
+        let c = 3. * mt * t * t;
+        let d = t * t * t;
+        contour.push((
+            a * start.0 + b * c0.0 + c * c1.0 + d * end.0,
+            a * start.1 + b * c0.1 + c * c1.1 + d * end.1,
+        ));
+    }
+}
+
+/// Generate a single-channel signed distance field from a glyph's contours.
+///
+/// Coverage is `128` on the outline, increasing towards `255` inside the glyph and decreasing
+/// towards `0` outside of it, reaching the extremes at `spread` pixels from the outline. The
+/// bitmap is padded by `spread` pixels on every side, which is included in the glyph's `top` and
+/// `left` bearings.
+///
+/// Overlapping contours and holes are resolved using the non-zero winding rule, just like
+/// DirectWrite does when filling glyphs.
+pub fn signed_distance_field(contours: &Contours, character: char, spread: f32) -> RasterizedGlyph {
+    let spread = spread.max(1.);
+    rasterize_by_distance(contours, character, spread.ceil(), |distance, winding| {
+        let distance = if winding == 0 { -distance } else { distance };
+        0.5 + distance / (2. * spread)
+    })
+}
+
+/// Rasterize the stroked outline of a glyph.
+///
+/// The stroke is `stroke_width` pixels wide and centered on the outline, so it extends half of
+/// its width to both sides. The bitmap is padded to fit the entire stroke, which is included in
+/// the glyph's `top` and `left` bearings.
+pub fn stroke(contours: &Contours, character: char, stroke_width: f32) -> RasterizedGlyph {
+    let half_width = stroke_width.max(0.) / 2.;
+    rasterize_by_distance(contours, character, half_width + 1., |distance, _| {
+        half_width + 0.5 - distance
+    })
+}
+
+/// Rasterize contours to a single channel bitmap, based on the distance to the outline.
+///
+/// The `coverage` of every pixel is computed from the distance between its center and the
+/// closest outline segment, together with its non-zero winding number. The bitmap covers the
+/// bounds of all contours, expanded by `padding` pixels on every side.
+fn rasterize_by_distance(
+    contours: &Contours,
+    character: char,
+    padding: f32,
+    coverage: impl Fn(f32, i32) -> f32,
+) -> RasterizedGlyph {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for &(x, y) in contours.iter().flatten() {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+
+    // Glyphs without outline, like whitespace, have no bitmap.
+    if min_x > max_x {
+        return RasterizedGlyph {
+            character,
+            buffer: BitmapBuffer::Alpha(Vec::new()),
+            ..Default::default()
+        };
+    }
+
+    let left = (min_x - padding).floor() as i32;
+    let top = (min_y - padding).floor() as i32;
+    let width = (max_x + padding).ceil() as i32 - left;
+    let height = (max_y + padding).ceil() as i32 - top;
+
+    let segments: Vec<(Point, Point)> = contours
+        .iter()
+        .flat_map(|contour| {
+            let closing = contour.last().zip(contour.first()).map(|(last, first)| (*last, *first));
+            contour.windows(2).map(|pair| (pair[0], pair[1])).chain(closing)
+        })
+        .collect();
+
+    let mut buffer = Vec::with_capacity((width * height) as usize);
+    for row in 0..height {
+        for column in 0..width {
+            let point = ((left + column) as f32 + 0.5, (top + row) as f32 + 0.5);
+
+            let mut distance = f32::MAX;
+            let mut winding = 0;
+            for &(start, end) in &segments {
+                distance = distance.min(segment_distance(point, start, end));
+                winding += segment_winding(point, start, end);
+            }
+
+            let coverage = coverage(distance, winding).clamp(0., 1.);
+            buffer.push((coverage * 255.).round() as u8);
+        }
+    }
+
+    RasterizedGlyph {
+        character,
+        width,
+        height,
+        top: -top,
+        left,
+        buffer: BitmapBuffer::Alpha(buffer),
+        ..Default::default()
+    }
+}
+
+/// Distance between a point and a line segment.
+fn segment_distance(point: Point, start: Point, end: Point) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+
+    let t = if length_squared == 0. {
+        0.
+    } else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0., 1.)
+    };
+
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+/// Contribution of a line segment to the winding number around a point.
+fn segment_winding(point: Point, start: Point, end: Point) -> i32 {
+    let cross = (end.0 - start.0) * (point.1 - start.1) - (point.0 - start.0) * (end.1 - start.1);
+
+    if start.1 <= point.1 {
+        (end.1 > point.1 && cross > 0.) as i32
+    } else {
+        -((end.1 <= point.1 && cross < 0.) as i32)
+    }
+}
+
+/// Outline builder recording the drawing commands.
+struct PathRecorder {
+    commands: Rc<RefCell<Vec<PathCommand>>>,
+}
+
+impl OutlineBuilder for PathRecorder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.commands.borrow_mut().push(PathCommand::MoveTo { x, y });
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.commands.borrow_mut().push(PathCommand::LineTo { x, y });
+    }
+
+    fn curve_to(&mut self, c0x: f32, c0y: f32, c1x: f32, c1y: f32, x: f32, y: f32) {
+        self.commands.borrow_mut().push(PathCommand::CubicTo { c0x, c0y, c1x, c1y, x, y });
+    }
+
+    fn close(&mut self) {
+        self.commands.borrow_mut().push(PathCommand::Close);
+    }
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(glyph: &RasterizedGlyph, x: i32, y: i32) -> u8 {
+        let BitmapBuffer::Alpha(buffer) = &glyph.buffer else { panic!("expected alpha buffer") };
+        let (row, column) = (y + glyph.top, x - glyph.left);
+        buffer[(row * glyph.width + column) as usize]
+    }
+
+    #[test]
+    fn sdf_handles_holes() {
+        let outer = vec![(0., 0.), (20., 0.), (20., 20.), (0., 20.)];
+        let inner = vec![(5., 5.), (5., 15.), (15., 15.), (15., 5.)];
+        let glyph = signed_distance_field(&vec![outer, inner], 'o', 4.);
+
+        // Inside the ring.
+        assert!(coverage(&glyph, 2, 10) > 128);
+        // Inside the hole.
+        assert!(coverage(&glyph, 10, 10) < 128);
+        // Outside of the glyph.
+        assert!(coverage(&glyph, -3, -3) < 128);
+    }
+
+    #[test]
+    fn stroke_covers_outline_only() {
+        let square = vec![(0., 0.), (20., 0.), (20., 20.), (0., 20.)];
+        let glyph = stroke(&vec![square], 'o', 4.);
+
+        // Stroke expansion is part of the bitmap.
+        assert!(glyph.left <= -3 && glyph.top >= 3);
+        assert!(glyph.width >= 26 && glyph.height >= 26);
+
+        // On the outline, on both sides of it and away from it.
+        assert_eq!(coverage(&glyph, 0, 10), 255);
+        assert_eq!(coverage(&glyph, 1, 10), 255);
+        assert_eq!(coverage(&glyph, -2, 10), 255);
+        assert_eq!(coverage(&glyph, 10, 10), 0);
+        assert_eq!(coverage(&glyph, -4, 10), 0);
+    }
+}
@@ -0,0 +1,373 @@
+//! Shaping of entire strings into positioned glyphs.
+
+#![allow(non_snake_case)]
+
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr;
+
+use dwrote::{FontFace, TextAnalysisSource};
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualGUID, REFIID};
+use winapi::shared::minwindef::{BOOL, ULONG};
+use winapi::shared::ntdef::HRESULT;
+use winapi::shared::winerror::{
+    ERROR_INSUFFICIENT_BUFFER, E_NOINTERFACE, HRESULT_FROM_WIN32, S_OK,
+};
+use winapi::um::dwrite::{
+    IDWriteNumberSubstitution, IDWriteTextAnalysisSink, IDWriteTextAnalysisSinkVtbl,
+    IDWriteTextAnalyzer, DWRITE_FONT_FEATURE, DWRITE_GLYPH_OFFSET, DWRITE_LINE_BREAKPOINT,
+    DWRITE_READING_DIRECTION_RIGHT_TO_LEFT, DWRITE_SCRIPT_ANALYSIS,
+    DWRITE_SHAPING_GLYPH_PROPERTIES, DWRITE_SHAPING_TEXT_PROPERTIES, DWRITE_TYPOGRAPHIC_FEATURES,
+};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::Interface;
+use wio::com::ComPtr;
+
+use super::{get_current_locale, get_dwrite3_factory, reading_direction, TextAnalysisSourceData};
+use crate::Error;
+
+/// Glyph produced by shaping, before rasterization.
+pub struct PositionedGlyph {
+    pub glyph_index: u16,
+    /// Byte offset of the first character of the cluster this glyph belongs to.
+    pub cluster: usize,
+    pub advance: f32,
+    /// Offset from the pen position, with the Y axis pointing up.
+    pub offset: (f32, f32),
+}
+
+/// Range of text using a single script.
+struct ScriptRun {
+    start: u32,
+    length: u32,
+    analysis: DWRITE_SCRIPT_ANALYSIS,
+}
+
+/// Text analysis sink collecting the script runs reported by `AnalyzeScript`.
+///
+/// The sink only lives on the stack for the duration of the analysis, so reference counting is a
+/// no-op.
+#[repr(C)]
+struct ScriptAnalysisSink {
+    // NB: This must be the first field.
+    vtbl: *const IDWriteTextAnalysisSinkVtbl,
+    runs: Vec<ScriptRun>,
+}
+
+static SCRIPT_ANALYSIS_SINK_VTBL: IDWriteTextAnalysisSinkVtbl = IDWriteTextAnalysisSinkVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: ScriptAnalysisSink_QueryInterface,
+        AddRef: ScriptAnalysisSink_AddRef,
+        Release: ScriptAnalysisSink_Release,
+    },
+    SetScriptAnalysis: ScriptAnalysisSink_SetScriptAnalysis,
+    SetLineBreakpoints: ScriptAnalysisSink_SetLineBreakpoints,
+    SetBidiLevel: ScriptAnalysisSink_SetBidiLevel,
+    SetNumberSubstitution: ScriptAnalysisSink_SetNumberSubstitution,
+};
+
+unsafe extern "system" fn ScriptAnalysisSink_QueryInterface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    object: *mut *mut c_void,
+) -> HRESULT {
+    if IsEqualGUID(&*riid, &IDWriteTextAnalysisSink::uuidof())
+        || IsEqualGUID(&*riid, &IUnknown::uuidof())
+    {
+        *object = this as *mut c_void;
+        S_OK
+    } else {
+        *object = ptr::null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn ScriptAnalysisSink_AddRef(_this: *mut IUnknown) -> ULONG {
+    1
+}
+
+unsafe extern "system" fn ScriptAnalysisSink_Release(_this: *mut IUnknown) -> ULONG {
+    1
+}
+
+unsafe extern "system" fn ScriptAnalysisSink_SetScriptAnalysis(
+    this: *mut IDWriteTextAnalysisSink,
+    start: u32,
+    length: u32,
+    analysis: *const DWRITE_SCRIPT_ANALYSIS,
+) -> HRESULT {
+    let sink = &mut *(this as *mut ScriptAnalysisSink);
+    sink.runs.push(ScriptRun { start, length, analysis: *analysis });
+    S_OK
+}
+
+unsafe extern "system" fn ScriptAnalysisSink_SetLineBreakpoints(
+    _this: *mut IDWriteTextAnalysisSink,
+    _start: u32,
+    _length: u32,
+    _breakpoints: *const DWRITE_LINE_BREAKPOINT,
+) -> HRESULT {
+    S_OK
+}
+
+unsafe extern "system" fn ScriptAnalysisSink_SetBidiLevel(
+    _this: *mut IDWriteTextAnalysisSink,
+    _start: u32,
+    _length: u32,
+    _explicit_level: u8,
+    _resolved_level: u8,
+) -> HRESULT {
+    S_OK
+}
+
+unsafe extern "system" fn ScriptAnalysisSink_SetNumberSubstitution(
+    _this: *mut IDWriteTextAnalysisSink,
+    _start: u32,
+    _length: u32,
+    _substitution: *mut IDWriteNumberSubstitution,
+) -> HRESULT {
+    S_OK
+}
+
+/// Shape a string into glyphs of a single font face.
+///
+/// Glyphs are returned in logical order.
+pub fn shape(text: &str, face: &FontFace, em_size: f32) -> Result<Vec<PositionedGlyph>, Error> {
+    shape_with_features(text, face, em_size, &[])
+}
+
+/// Shape a string with additional OpenType features enabled, like `smcp` for small capitals.
+///
+/// The features are applied to the entire string, on top of the default features of the script.
+pub fn shape_with_features(
+    text: &str,
+    face: &FontFace,
+    em_size: f32,
+    features: &[[u8; 4]],
+) -> Result<Vec<PositionedGlyph>, Error> {
+    let utf16: Vec<u16> = text.encode_utf16().collect();
+    if utf16.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Byte offset in `text` for every UTF-16 code unit.
+    let byte_offsets: Vec<usize> = text
+        .char_indices()
+        .flat_map(|(offset, character)| std::iter::repeat(offset).take(character.len_utf16()))
+        .collect();
+
+    let factory3 = get_dwrite3_factory().ok_or(Error::Unsupported("IDWriteFactory3"))?;
+    let analyzer = unsafe {
+        let mut native: *mut IDWriteTextAnalyzer = ptr::null_mut();
+        let hr = factory3.CreateTextAnalyzer(&mut native);
+        if hr != S_OK || native.is_null() {
+            return Err(Error::from(hr));
+        }
+        ComPtr::from_raw(native)
+    };
+
+    let locale = get_current_locale();
+    let wide_locale: Vec<u16> = OsStr::new(&locale).encode_wide().chain(Some(0)).collect();
+
+    let length = utf16.len() as u32;
+    let direction = reading_direction(text.chars());
+    let text_analysis_source_data = TextAnalysisSourceData { locale: &locale, length, direction };
+    let text_analysis_source =
+        TextAnalysisSource::from_text(Box::new(text_analysis_source_data), Cow::Borrowed(&utf16));
+
+    let mut sink = ScriptAnalysisSink { vtbl: &SCRIPT_ANALYSIS_SINK_VTBL, runs: Vec::new() };
+    let hr = unsafe {
+        analyzer.AnalyzeScript(
+            text_analysis_source.as_ptr(),
+            0,
+            length,
+            &mut sink as *mut ScriptAnalysisSink as *mut IDWriteTextAnalysisSink,
+        )
+    };
+    if hr != S_OK {
+        return Err(Error::from(hr));
+    }
+
+    let mut runs = sink.runs;
+    runs.sort_by_key(|run| run.start);
+
+    let mut glyphs = Vec::new();
+    for run in runs {
+        let start = run.start as usize;
+        let run_text = &utf16[start..start + run.length as usize];
+        let run_glyphs =
+            shape_run(&analyzer, face, em_size, run_text, &run, &wide_locale, features)?;
+        glyphs.extend(run_glyphs.into_iter().map(|mut glyph| {
+            glyph.cluster = byte_offsets[start + glyph.cluster];
+            glyph
+        }));
+    }
+
+    Ok(glyphs)
+}
+
+/// Shape a single script run.
+///
+/// The clusters of the returned glyphs are UTF-16 offsets relative to the start of the run.
+fn shape_run(
+    analyzer: &ComPtr<IDWriteTextAnalyzer>,
+    face: &FontFace,
+    em_size: f32,
+    text: &[u16],
+    run: &ScriptRun,
+    locale: &[u16],
+    features: &[[u8; 4]],
+) -> Result<Vec<PositionedGlyph>, Error> {
+    let length = text.len();
+
+    // A single feature range covering the entire run.
+    let mut font_features: Vec<DWRITE_FONT_FEATURE> = features
+        .iter()
+        .map(|tag| DWRITE_FONT_FEATURE { nameTag: u32::from_le_bytes(*tag), parameter: 1 })
+        .collect();
+    let typographic_features = DWRITE_TYPOGRAPHIC_FEATURES {
+        features: font_features.as_mut_ptr(),
+        featureCount: font_features.len() as u32,
+    };
+    let mut feature_ranges = [&typographic_features as *const DWRITE_TYPOGRAPHIC_FEATURES];
+    let feature_range_lengths = [length as u32];
+    let (feature_ranges, feature_range_count) =
+        if features.is_empty() { (ptr::null_mut(), 0) } else { (feature_ranges.as_mut_ptr(), 1) };
+
+    // Right to left runs need mirrored glyphs, like for parentheses in Arabic text.
+    let characters = char::decode_utf16(text.iter().copied()).filter_map(Result::ok);
+    let is_right_to_left =
+        (reading_direction(characters) == DWRITE_READING_DIRECTION_RIGHT_TO_LEFT) as BOOL;
+
+    let mut cluster_map = vec![0u16; length];
+    let mut text_props = vec![DWRITE_SHAPING_TEXT_PROPERTIES { bit_fields: 0 }; length];
+
+    // Recommended initial buffer size, grown if shaping produces more glyphs.
+    let mut max_glyph_count = 3 * length / 2 + 16;
+    let (glyph_indices, glyph_props) = loop {
+        let mut glyph_indices = vec![0u16; max_glyph_count];
+        let mut glyph_props =
+            vec![DWRITE_SHAPING_GLYPH_PROPERTIES { bit_fields: 0 }; max_glyph_count];
+        let mut glyph_count = 0;
+
+        let hr = unsafe {
+            analyzer.GetGlyphs(
+                text.as_ptr(),
+                length as u32,
+                face.as_ptr(),
+                0,
+                is_right_to_left,
+                &run.analysis,
+                locale.as_ptr(),
+                ptr::null_mut(),
+                feature_ranges,
+                feature_range_lengths.as_ptr(),
+                feature_range_count,
+                max_glyph_count as u32,
+                cluster_map.as_mut_ptr(),
+                text_props.as_mut_ptr(),
+                glyph_indices.as_mut_ptr(),
+                glyph_props.as_mut_ptr(),
+                &mut glyph_count,
+            )
+        };
+
+        if hr == HRESULT_FROM_WIN32(ERROR_INSUFFICIENT_BUFFER) {
+            max_glyph_count *= 2;
+            continue;
+        } else if hr != S_OK {
+            return Err(Error::from(hr));
+        }
+
+        glyph_indices.truncate(glyph_count as usize);
+        glyph_props.truncate(glyph_count as usize);
+        break (glyph_indices, glyph_props);
+    };
+
+    let glyph_count = glyph_indices.len();
+    let mut advances = vec![0f32; glyph_count];
+    let mut offsets =
+        vec![DWRITE_GLYPH_OFFSET { advanceOffset: 0., ascenderOffset: 0. }; glyph_count];
+
+    let hr = unsafe {
+        analyzer.GetGlyphPlacements(
+            text.as_ptr(),
+            cluster_map.as_ptr(),
+            text_props.as_mut_ptr(),
+            length as u32,
+            glyph_indices.as_ptr(),
+            glyph_props.as_ptr(),
+            glyph_count as u32,
+            face.as_ptr(),
+            em_size,
+            0,
+            is_right_to_left,
+            &run.analysis,
+            locale.as_ptr(),
+            feature_ranges,
+            feature_range_lengths.as_ptr(),
+            feature_range_count,
+            advances.as_mut_ptr(),
+            offsets.as_mut_ptr(),
+        )
+    };
+    if hr != S_OK {
+        return Err(Error::from(hr));
+    }
+
+    let clusters = glyph_clusters(&cluster_map, glyph_count);
+
+    Ok(glyph_indices
+        .into_iter()
+        .zip(clusters)
+        .zip(advances.into_iter().zip(offsets))
+        .map(|((glyph_index, cluster), (advance, offset))| PositionedGlyph {
+            glyph_index,
+            cluster,
+            advance,
+            offset: (offset.advanceOffset, offset.ascenderOffset),
+        })
+        .collect())
+}
+
+/// Invert a cluster map, resolving the text offset of the cluster each glyph belongs to.
+///
+/// The cluster map stores the first glyph of the cluster for every UTF-16 code unit.
+fn glyph_clusters(cluster_map: &[u16], glyph_count: usize) -> Vec<usize> {
+    let mut clusters = vec![0; glyph_count];
+
+    let mut position = 0;
+    while position < cluster_map.len() {
+        let first_glyph = cluster_map[position];
+
+        let mut end = position + 1;
+        while end < cluster_map.len() && cluster_map[end] == first_glyph {
+            end += 1;
+        }
+
+        let last_glyph = cluster_map.get(end).map_or(glyph_count, |&glyph| glyph as usize);
+        for cluster in clusters.iter_mut().take(last_glyph).skip(first_glyph as usize) {
+            *cluster = position;
+        }
+
+        position = end;
+    }
+
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ligature_clusters() {
+        // "ffi" ligature followed by a single glyph.
+        assert_eq!(glyph_clusters(&[0, 0, 0, 1], 2), vec![0, 3]);
+
+        // Decomposed character with a base and a mark glyph.
+        assert_eq!(glyph_clusters(&[0, 2], 3), vec![0, 0, 1]);
+    }
+}
@@ -0,0 +1,475 @@
+//! Parsing of raw OpenType tables which DirectWrite doesn't expose directly.
+
+use crate::FontVariation;
+
+/// DirectWrite tag of the font variations table.
+pub const FVAR: u32 = u32::from_le_bytes(*b"fvar");
+
+/// DirectWrite tag of the naming table.
+pub const NAME: u32 = u32::from_le_bytes(*b"name");
+
+/// DirectWrite tag of the character to glyph mapping table.
+pub const CMAP: u32 = u32::from_le_bytes(*b"cmap");
+
+/// DirectWrite tag of the color palette table.
+pub const CPAL: u32 = u32::from_le_bytes(*b"CPAL");
+
+/// DirectWrite tags of the embedded bitmap location and data tables.
+pub const EBLC: u32 = u32::from_le_bytes(*b"EBLC");
+pub const EBDT: u32 = u32::from_le_bytes(*b"EBDT");
+
+/// DirectWrite tags of the color bitmap location and data tables.
+pub const CBLC: u32 = u32::from_le_bytes(*b"CBLC");
+pub const CBDT: u32 = u32::from_le_bytes(*b"CBDT");
+
+/// DirectWrite tags of the glyph outline tables.
+pub const GLYF: u32 = u32::from_le_bytes(*b"glyf");
+pub const CFF: u32 = u32::from_le_bytes(*b"CFF ");
+pub const CFF2: u32 = u32::from_le_bytes(*b"CFF2");
+
+/// DirectWrite tags of the horizontal header and OS/2 tables.
+pub const HHEA: u32 = u32::from_le_bytes(*b"hhea");
+pub const OS2: u32 = u32::from_le_bytes(*b"OS/2");
+
+/// DirectWrite tag of the vertical header table.
+pub const VHEA: u32 = u32::from_le_bytes(*b"vhea");
+
+/// DirectWrite tags of the glyph substitution and positioning tables.
+pub const GSUB: u32 = u32::from_le_bytes(*b"GSUB");
+pub const GPOS: u32 = u32::from_le_bytes(*b"GPOS");
+
+/// `fsSelection` flag of the OS/2 table to prefer the typographic metrics.
+const USE_TYPO_METRICS: u16 = 1 << 7;
+
+/// Name IDs of the naming table.
+pub const COPYRIGHT_NAME_ID: u16 = 0;
+pub const FAMILY_NAME_ID: u16 = 1;
+pub const FULL_NAME_ID: u16 = 4;
+pub const VERSION_NAME_ID: u16 = 5;
+pub const POSTSCRIPT_NAME_ID: u16 = 6;
+pub const DESIGNER_NAME_ID: u16 = 9;
+pub const LICENSE_NAME_ID: u16 = 13;
+pub const LICENSE_URL_NAME_ID: u16 = 14;
+
+/// Windows platform ID in the naming table.
+const PLATFORM_WINDOWS: u16 = 3;
+
+/// Unicode platform ID in the naming table.
+const PLATFORM_UNICODE: u16 = 0;
+
+/// Windows language ID for US English.
+const LANGUAGE_EN_US: u16 = 0x0409;
+
+/// Windows encoding ID of the full repertoire `cmap` subtable.
+const ENCODING_WINDOWS_UNICODE_FULL: u16 = 10;
+
+/// Unicode encoding IDs of the full repertoire `cmap` subtables.
+const ENCODING_UNICODE_FULL: [u16; 2] = [4, 6];
+
+/// Unicode encoding ID of the variation sequences `cmap` subtable.
+const ENCODING_UNICODE_VARIATION_SEQUENCES: u16 = 5;
+
+/// Glyph selected by a Unicode variation sequence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VariationGlyph {
+    /// The sequence uses the base character's default glyph.
+    Default,
+
+    /// The sequence uses a dedicated glyph.
+    Glyph(u16),
+}
+
+/// Named instance of a variable font.
+#[derive(Debug, Clone)]
+pub struct FvarInstance {
+    /// Name ID of the instance's subfamily name, like `SemiBold`.
+    pub name_id: u16,
+
+    /// Coordinates of the instance for every axis of the font.
+    pub coordinates: Vec<FontVariation>,
+}
+
+/// Font-wide vertical metrics in design units.
+#[derive(Debug, Copy, Clone)]
+pub struct VerticalHeader {
+    pub ascent: i16,
+    pub descent: i16,
+    pub line_gap: i16,
+}
+
+/// Get the vertical metrics from a `vhea` table.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/vhea.
+pub fn vertical_header(vhea: &[u8]) -> Option<VerticalHeader> {
+    Some(VerticalHeader {
+        ascent: read_u16(vhea, 4)? as i16,
+        descent: read_u16(vhea, 6)? as i16,
+        line_gap: read_u16(vhea, 8)? as i16,
+    })
+}
+
+/// Font-wide horizontal line metrics in design units.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineMetrics {
+    pub ascent: i16,
+
+    /// Distance from the baseline to the bottom of descenders, which is always negative.
+    pub descent: i16,
+
+    pub line_gap: i16,
+}
+
+/// Get the line metrics from a `hhea` table.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/hhea.
+pub fn horizontal_line_metrics(hhea: &[u8]) -> Option<LineMetrics> {
+    read_line_metrics(hhea, 4)
+}
+
+/// Get the typographic line metrics from an `OS/2` table.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/os2.
+pub fn typo_line_metrics(os2: &[u8]) -> Option<LineMetrics> {
+    read_line_metrics(os2, 68)
+}
+
+/// Read consecutive ascent, descent and line gap values.
+///
+/// The descent must be negative according to the OpenType spec, but some fonts store it as a
+/// positive distance below the baseline instead. Both encodings result in a negative descent.
+fn read_line_metrics(table: &[u8], offset: usize) -> Option<LineMetrics> {
+    let descent = read_u16(table, offset + 2)? as i16;
+    Some(LineMetrics {
+        ascent: read_u16(table, offset)? as i16,
+        descent: -descent.saturating_abs(),
+        line_gap: read_u16(table, offset + 4)? as i16,
+    })
+}
+
+/// Check if an `OS/2` table asks for its typographic metrics to be used for line layout.
+pub fn use_typo_metrics(os2: &[u8]) -> bool {
+    read_u16(os2, 62).is_some_and(|fs_selection| fs_selection & USE_TYPO_METRICS != 0)
+}
+
+/// Get the number of palettes in a `CPAL` table.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/cpal.
+pub fn palette_count(cpal: &[u8]) -> Option<u16> {
+    read_u16(cpal, 4)
+}
+
+/// Get all named instances from an `fvar` table.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/fvar.
+pub fn fvar_instances(fvar: &[u8]) -> Vec<FvarInstance> {
+    fvar_instances_inner(fvar).unwrap_or_default()
+}
+
+fn fvar_instances_inner(fvar: &[u8]) -> Option<Vec<FvarInstance>> {
+    let axes_offset = usize::from(read_u16(fvar, 4)?);
+    let axis_count = usize::from(read_u16(fvar, 8)?);
+    let axis_size = usize::from(read_u16(fvar, 10)?);
+    let instance_count = usize::from(read_u16(fvar, 12)?);
+    let instance_size = usize::from(read_u16(fvar, 14)?);
+
+    let tags = (0..axis_count)
+        .map(|axis| {
+            let offset = axes_offset + axis * axis_size;
+            fvar.get(offset..offset + 4)?.try_into().ok()
+        })
+        .collect::<Option<Vec<[u8; 4]>>>()?;
+
+    let instances_offset = axes_offset + axis_count * axis_size;
+    let instances = (0..instance_count)
+        .map(|instance| {
+            let offset = instances_offset + instance * instance_size;
+            let name_id = read_u16(fvar, offset)?;
+
+            let coordinates = tags
+                .iter()
+                .enumerate()
+                .map(|(axis, tag)| {
+                    let value = read_fixed(fvar, offset + 4 + axis * 4)?;
+                    Some(FontVariation::new(*tag, value))
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(FvarInstance { name_id, coordinates })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(instances)
+}
+
+/// Get a string from a `name` table.
+///
+/// English names for the Windows platform are preferred, falling back to any other Unicode
+/// string with the same ID.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/name.
+pub fn name_string(name: &[u8], name_id: u16) -> Option<String> {
+    let count = usize::from(read_u16(name, 2)?);
+    let storage_offset = usize::from(read_u16(name, 4)?);
+
+    let mut fallback = None;
+    for record in 0..count {
+        let offset = 6 + record * 12;
+        let platform_id = read_u16(name, offset)?;
+        let language_id = read_u16(name, offset + 4)?;
+
+        if read_u16(name, offset + 6)? != name_id
+            || (platform_id != PLATFORM_WINDOWS && platform_id != PLATFORM_UNICODE)
+        {
+            continue;
+        }
+
+        let length = usize::from(read_u16(name, offset + 8)?);
+        let start = storage_offset + usize::from(read_u16(name, offset + 10)?);
+        let string = match name.get(start..start + length) {
+            Some(string) => string,
+            None => continue,
+        };
+
+        if platform_id == PLATFORM_WINDOWS && language_id == LANGUAGE_EN_US {
+            return Some(decode_utf16_be(string));
+        } else if fallback.is_none() {
+            fallback = Some(string);
+        }
+    }
+
+    fallback.map(decode_utf16_be)
+}
+
+/// Get the glyph for a variation sequence from a `cmap` table.
+///
+/// Returns `None` if the font doesn't list the sequence in its format 14 subtable.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/cmap.
+pub fn variation_glyph(cmap: &[u8], base: char, selector: char) -> Option<VariationGlyph> {
+    let (base, selector) = (base as u32, selector as u32);
+
+    let table_count = usize::from(read_u16(cmap, 2)?);
+    let subtable = (0..table_count).find_map(|table| {
+        let offset = 4 + table * 8;
+        let platform_id = read_u16(cmap, offset)?;
+        let encoding_id = read_u16(cmap, offset + 2)?;
+        let subtable = read_u32(cmap, offset + 4)? as usize;
+
+        let is_variation_subtable = platform_id == PLATFORM_UNICODE
+            && encoding_id == ENCODING_UNICODE_VARIATION_SEQUENCES
+            && read_u16(cmap, subtable)? == 14;
+        is_variation_subtable.then_some(subtable)
+    })?;
+
+    let record_count = read_u32(cmap, subtable + 6)? as usize;
+    let record = (0..record_count)
+        .map(|record| subtable + 10 + record * 11)
+        .find(|&record| read_u24(cmap, record) == Some(selector))?;
+
+    let default_offset = read_u32(cmap, record + 3)? as usize;
+    if default_offset != 0 {
+        let ranges = subtable + default_offset;
+        let range_count = read_u32(cmap, ranges)? as usize;
+        for range in 0..range_count {
+            let offset = ranges + 4 + range * 4;
+            let start = read_u24(cmap, offset)?;
+            let additional_count = u32::from(*cmap.get(offset + 3)?);
+            if (start..=start + additional_count).contains(&base) {
+                return Some(VariationGlyph::Default);
+            }
+        }
+    }
+
+    let non_default_offset = read_u32(cmap, record + 7)? as usize;
+    if non_default_offset != 0 {
+        let mappings = subtable + non_default_offset;
+        let mapping_count = read_u32(cmap, mappings)? as usize;
+        for mapping in 0..mapping_count {
+            let offset = mappings + 4 + mapping * 5;
+            if read_u24(cmap, offset)? == base {
+                return Some(VariationGlyph::Glyph(read_u16(cmap, offset + 3)?));
+            }
+        }
+    }
+
+    None
+}
+
+/// Get the glyph of a character from the full repertoire subtables of a `cmap` table.
+///
+/// Only segmented coverage (format 12) and many-to-one range (format 13) subtables are used,
+/// which are the only ones able to map characters outside of the Basic Multilingual Plane.
+///
+/// Returns `None` if the character is not mapped.
+pub fn full_repertoire_glyph(cmap: &[u8], character: char) -> Option<u16> {
+    let character = character as u32;
+
+    let table_count = usize::from(read_u16(cmap, 2)?);
+    (0..table_count).find_map(|table| {
+        let offset = 4 + table * 8;
+        let platform_id = read_u16(cmap, offset)?;
+        let encoding_id = read_u16(cmap, offset + 2)?;
+        let subtable = read_u32(cmap, offset + 4)? as usize;
+
+        let is_full_repertoire = (platform_id == PLATFORM_WINDOWS
+            && encoding_id == ENCODING_WINDOWS_UNICODE_FULL)
+            || (platform_id == PLATFORM_UNICODE && ENCODING_UNICODE_FULL.contains(&encoding_id));
+        if !is_full_repertoire {
+            return None;
+        }
+
+        let format = read_u16(cmap, subtable)?;
+        if format != 12 && format != 13 {
+            return None;
+        }
+
+        let group_count = read_u32(cmap, subtable + 12)? as usize;
+        (0..group_count).find_map(|group| {
+            let offset = subtable + 16 + group * 12;
+            let start = read_u32(cmap, offset)?;
+            let end = read_u32(cmap, offset + 4)?;
+            if !(start..=end).contains(&character) {
+                return None;
+            }
+
+            let glyph = read_u32(cmap, offset + 8)?;
+            let glyph = if format == 12 { glyph + character - start } else { glyph };
+            u16::try_from(glyph).ok().filter(|&glyph| glyph != 0)
+        })
+    })
+}
+
+/// Get the script tags of a `GSUB` or `GPOS` table's script list.
+///
+/// See https://learn.microsoft.com/en-us/typography/opentype/spec/chapter2.
+pub fn script_tags(layout: &[u8]) -> Vec<[u8; 4]> {
+    script_tags_inner(layout).unwrap_or_default()
+}
+
+fn script_tags_inner(layout: &[u8]) -> Option<Vec<[u8; 4]>> {
+    let script_list = usize::from(read_u16(layout, 4)?);
+    let count = usize::from(read_u16(layout, script_list)?);
+
+    (0..count)
+        .map(|record| {
+            let offset = script_list + 2 + record * 6;
+            layout.get(offset..offset + 4)?.try_into().ok()
+        })
+        .collect()
+}
+
+fn decode_utf16_be(bytes: &[u8]) -> String {
+    let units = bytes.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+    char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u24(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 3)?;
+    Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Read a 16.16 fixed-point number.
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    Some(read_u32(data, offset)? as i32 as f32 / 65536.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cmap_variation_sequences() {
+        let mut cmap = vec![0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0, 12];
+
+        // Format 14 subtable with records for VS15 and VS16.
+        cmap.extend_from_slice(&[0, 14, 0, 0, 0, 0, 0, 0, 0, 2]);
+        cmap.extend_from_slice(&[0x00, 0xfe, 0x0e, 0, 0, 0, 32, 0, 0, 0, 0]);
+        cmap.extend_from_slice(&[0x00, 0xfe, 0x0f, 0, 0, 0, 0, 0, 0, 0, 40]);
+
+        // Default UVS: U+260E.
+        cmap.extend_from_slice(&[0, 0, 0, 1, 0x00, 0x26, 0x0e, 0]);
+
+        // Non-default UVS: U+260E => glyph 42.
+        cmap.extend_from_slice(&[0, 0, 0, 1, 0x00, 0x26, 0x0e, 0, 42]);
+
+        assert_eq!(variation_glyph(&cmap, '\u{260e}', '\u{fe0e}'), Some(VariationGlyph::Default));
+        assert_eq!(variation_glyph(&cmap, '\u{260e}', '\u{fe0f}'), Some(VariationGlyph::Glyph(42)));
+        assert_eq!(variation_glyph(&cmap, 'a', '\u{fe0f}'), None);
+        assert_eq!(variation_glyph(&cmap, '\u{260e}', '\u{fe00}'), None);
+    }
+
+    #[test]
+    fn cmap_full_repertoire() {
+        let mut cmap = vec![0, 0, 0, 2, 0, 3, 0, 10, 0, 0, 0, 20, 0, 0, 0, 6, 0, 0, 0, 60];
+
+        // Format 12 subtable: U+61 => glyph 5, U+1D400..=U+1D401 => glyphs 10 and 11.
+        cmap.extend_from_slice(&[0, 12, 0, 0, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 2]);
+        cmap.extend_from_slice(&[0, 0, 0, 0x61, 0, 0, 0, 0x61, 0, 0, 0, 5]);
+        cmap.extend_from_slice(&[0, 1, 0xd4, 0, 0, 1, 0xd4, 1, 0, 0, 0, 10]);
+
+        // Format 13 subtable: U+1F600..=U+1F602 => glyph 7.
+        cmap.extend_from_slice(&[0, 13, 0, 0, 0, 0, 0, 28, 0, 0, 0, 0, 0, 0, 0, 1]);
+        cmap.extend_from_slice(&[0, 1, 0xf6, 0, 0, 1, 0xf6, 2, 0, 0, 0, 7]);
+
+        assert_eq!(full_repertoire_glyph(&cmap, '\u{1d400}'), Some(10));
+        assert_eq!(full_repertoire_glyph(&cmap, '\u{1d401}'), Some(11));
+        assert_eq!(full_repertoire_glyph(&cmap, 'a'), Some(5));
+        assert_eq!(full_repertoire_glyph(&cmap, '\u{1f602}'), Some(7));
+        assert_eq!(full_repertoire_glyph(&cmap, '\u{1d402}'), None);
+    }
+
+    #[test]
+    fn layout_script_tags() {
+        let mut gsub = vec![0, 1, 0, 0, 0, 10, 0, 0, 0, 0];
+        gsub.extend_from_slice(&[0, 2, b'a', b'r', b'a', b'b', 0, 0, b'l', b'a', b't', b'n', 0, 0]);
+
+        assert_eq!(script_tags(&gsub), vec![*b"arab", *b"latn"]);
+        assert_eq!(script_tags(&gsub[..12]), Vec::<[u8; 4]>::new());
+    }
+
+    #[test]
+    fn os2_typo_metrics() {
+        let mut os2 = vec![0; 78];
+        os2[62..64].copy_from_slice(&0x0080u16.to_be_bytes());
+        os2[68..70].copy_from_slice(&800i16.to_be_bytes());
+        os2[70..72].copy_from_slice(&(-200i16).to_be_bytes());
+        os2[72..74].copy_from_slice(&100i16.to_be_bytes());
+
+        let expected = LineMetrics { ascent: 800, descent: -200, line_gap: 100 };
+        assert_eq!(typo_line_metrics(&os2), Some(expected));
+        assert!(use_typo_metrics(&os2));
+
+        os2[63] = 0;
+        assert!(!use_typo_metrics(&os2));
+        assert_eq!(typo_line_metrics(&os2[..68]), None);
+    }
+
+    #[test]
+    fn hhea_descent_encodings() {
+        let mut hhea = vec![0; 36];
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[8..10].copy_from_slice(&100i16.to_be_bytes());
+
+        let expected = LineMetrics { ascent: 800, descent: -200, line_gap: 100 };
+        assert_eq!(horizontal_line_metrics(&hhea), Some(expected));
+
+        // Positive descents are distances below the baseline too.
+        hhea[6..8].copy_from_slice(&200i16.to_be_bytes());
+        assert_eq!(horizontal_line_metrics(&hhea), Some(expected));
+
+        let mut os2 = vec![0; 78];
+        os2[68..70].copy_from_slice(&800i16.to_be_bytes());
+        os2[70..72].copy_from_slice(&200i16.to_be_bytes());
+        os2[72..74].copy_from_slice(&100i16.to_be_bytes());
+        assert_eq!(typo_line_metrics(&os2), Some(expected));
+    }
+}
@@ -0,0 +1,100 @@
+//! Synthesized glyphs for characters missing from all fonts.
+
+use crate::{BitmapBuffer, RasterizedGlyph};
+
+/// Width of a hex digit in font pixels.
+const DIGIT_WIDTH: i32 = 3;
+
+/// Height of a hex digit in font pixels.
+const DIGIT_HEIGHT: i32 = 5;
+
+/// Bitmaps of the hex digits `0-F`, one row per byte with the leftmost pixel in bit 2.
+const DIGITS: [[u8; DIGIT_HEIGHT as usize]; 16] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+    [0b010, 0b101, 0b111, 0b101, 0b101],
+    [0b110, 0b101, 0b110, 0b101, 0b110],
+    [0b011, 0b100, 0b100, 0b100, 0b011],
+    [0b110, 0b101, 0b101, 0b101, 0b110],
+    [0b111, 0b100, 0b110, 0b100, 0b111],
+    [0b111, 0b100, 0b110, 0b100, 0b100],
+];
+
+/// Create a box sitting on the baseline, showing the character's codepoint in hexadecimal.
+///
+/// The digits are split over two rows and scaled to fill the box. Digits which don't fit into
+/// very small boxes are clipped.
+pub fn hex_box(character: char, width: i32, height: i32, advance: (i32, i32)) -> RasterizedGlyph {
+    let (width, height) = (width.max(1), height.max(1));
+    let mut pixels = vec![false; (width * height) as usize];
+    let mut set_pixel = |x: i32, y: i32| {
+        if (0..width).contains(&x) && (0..height).contains(&y) {
+            pixels[(y * width + x) as usize] = true;
+        }
+    };
+
+    // Border.
+    for x in 0..width {
+        set_pixel(x, 0);
+        set_pixel(x, height - 1);
+    }
+    for y in 0..height {
+        set_pixel(0, y);
+        set_pixel(width - 1, y);
+    }
+
+    let digits: Vec<usize> = format!("{:04X}", character as u32)
+        .chars()
+        .filter_map(|digit| digit.to_digit(16))
+        .map(|digit| digit as usize)
+        .collect();
+    let columns = (digits.len() as i32 + 1) / 2;
+
+    // Leave one pixel of padding inside the border.
+    let text_width = columns * (DIGIT_WIDTH + 1) - 1;
+    let text_height = 2 * DIGIT_HEIGHT + 1;
+    let scale = ((width - 4) / text_width).min((height - 4) / text_height).max(1);
+    let x_offset = ((width - text_width * scale) / 2).max(2);
+    let y_offset = ((height - text_height * scale) / 2).max(2);
+
+    for (i, &digit) in digits.iter().enumerate() {
+        let (row, column) = (i as i32 / columns, i as i32 % columns);
+        let digit_x = x_offset + column * (DIGIT_WIDTH + 1) * scale;
+        let digit_y = y_offset + row * (DIGIT_HEIGHT + 1) * scale;
+
+        for (y, bits) in DIGITS[digit].iter().enumerate() {
+            for x in 0..DIGIT_WIDTH {
+                if bits & (1 << (DIGIT_WIDTH - 1 - x)) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        set_pixel(digit_x + x * scale + dx, digit_y + y as i32 * scale + dy);
+                    }
+                }
+            }
+        }
+    }
+
+    let buffer = pixels.iter().flat_map(|&pixel| [if pixel { 255 } else { 0 }; 3]).collect();
+
+    RasterizedGlyph {
+        character,
+        width,
+        height,
+        top: height,
+        left: 0,
+        advance,
+        buffer: BitmapBuffer::Rgb(buffer),
+        ..Default::default()
+    }
+}
@@ -140,7 +140,7 @@ pub struct HintStylePropertyIter<'a> {
 }
 
 impl<'a> HintStylePropertyIter<'a> {
-    fn new(pattern: &PatternRef) -> HintStylePropertyIter {
+    fn new(pattern: &PatternRef) -> HintStylePropertyIter<'_> {
         HintStylePropertyIter { inner: IntPropertyIter::new(pattern, b"hintstyle\0") }
     }
 
@@ -167,7 +167,7 @@ pub struct LcdFilterPropertyIter<'a> {
 }
 
 impl<'a> LcdFilterPropertyIter<'a> {
-    fn new(pattern: &PatternRef) -> LcdFilterPropertyIter {
+    fn new(pattern: &PatternRef) -> LcdFilterPropertyIter<'_> {
         LcdFilterPropertyIter { inner: IntPropertyIter::new(pattern, b"lcdfilter\0") }
     }
 
@@ -329,7 +329,7 @@ macro_rules! string_accessor {
             }
 
             #[inline]
-            pub fn $getter(&self) -> StringPropertyIter {
+            pub fn $getter(&self) -> StringPropertyIter<'_> {
                 unsafe {
                     self.get_string($object_name)
                 }
@@ -368,7 +368,7 @@ impl Default for Pattern {
 macro_rules! pattern_get_integer {
     ($($method:ident() => $property:expr),+) => {
         $(
-            pub fn $method(&self) -> IntPropertyIter {
+            pub fn $method(&self) -> IntPropertyIter<'_> {
                 unsafe {
                     self.get_integer($property)
                 }
@@ -380,7 +380,7 @@ macro_rules! pattern_get_integer {
 macro_rules! boolean_getter {
     ($($method:ident() => $property:expr),*) => {
         $(
-            pub fn $method(&self) -> BooleanPropertyIter {
+            pub fn $method(&self) -> BooleanPropertyIter<'_> {
                 unsafe {
                     self.get_boolean($property)
                 }
@@ -392,7 +392,7 @@ macro_rules! boolean_getter {
 macro_rules! double_getter {
     ($($method:ident() => $property:expr),*) => {
         $(
-            pub fn $method(&self) -> DoublePropertyIter {
+            pub fn $method(&self) -> DoublePropertyIter<'_> {
                 unsafe {
                     self.get_double($property)
                 }
@@ -495,11 +495,11 @@ impl PatternRef {
         BooleanPropertyIter::new(self, object)
     }
 
-    pub fn hintstyle(&self) -> HintStylePropertyIter {
+    pub fn hintstyle(&self) -> HintStylePropertyIter<'_> {
         HintStylePropertyIter::new(self)
     }
 
-    pub fn lcdfilter(&self) -> LcdFilterPropertyIter {
+    pub fn lcdfilter(&self) -> LcdFilterPropertyIter<'_> {
         LcdFilterPropertyIter::new(self)
     }
 
@@ -523,7 +523,7 @@ impl PatternRef {
         unsafe { self.get_integer(b"width\0").next().map(Width::from) }
     }
 
-    pub fn rgba(&self) -> RgbaPropertyIter {
+    pub fn rgba(&self) -> RgbaPropertyIter<'_> {
         RgbaPropertyIter::new(self, b"rgba\0")
     }
 
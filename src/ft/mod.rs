@@ -174,14 +174,24 @@ impl Rasterize for FreeTypeRasterizer {
                 },
             };
 
+        let (overline_position, overline_thickness) =
+            Metrics::overline(ascent, underline_thickness);
+
+        let double_underline_position = underline_position - 2. * underline_thickness;
+
         Ok(Metrics {
             average_advance: full.cell_width,
             line_height: height,
+            ascent,
             descent,
+            line_gap: (height - global_glyph_height) as f32,
             underline_position,
             underline_thickness,
             strikeout_position,
             strikeout_thickness,
+            overline_position,
+            overline_thickness,
+            double_underline_position,
         })
     }
 
@@ -246,6 +256,11 @@ impl Rasterize for FreeTypeRasterizer {
         let (pixel_height, pixel_width, buffer) =
             Self::normalize_buffer(&glyph.bitmap(), &face.rgba)?;
 
+        let metrics = glyph.metrics();
+        let left_side_bearing = from_freetype_26_6(metrics.horiBearingX);
+        let right_side_bearing =
+            from_freetype_26_6(metrics.horiAdvance - metrics.horiBearingX - metrics.width);
+
         let mut rasterized_glyph = RasterizedGlyph {
             character: glyph_key.character,
             top: glyph.bitmap_top(),
@@ -253,6 +268,7 @@ impl Rasterize for FreeTypeRasterizer {
             width: pixel_width,
             height: pixel_height,
             advance,
+            side_bearings: (left_side_bearing, right_side_bearing),
             buffer,
         };
 
@@ -273,6 +289,8 @@ impl Rasterize for FreeTypeRasterizer {
             // Scale glyph advance.
             rasterized_glyph.advance.0 = (advance.0 as f64 * fixup_factor).round() as i32;
             rasterized_glyph.advance.1 = (advance.1 as f64 * fixup_factor).round() as i32;
+            rasterized_glyph.side_bearings.0 *= fixup_factor as f32;
+            rasterized_glyph.side_bearings.1 *= fixup_factor as f32;
 
             rasterized_glyph = downsample_bitmap(rasterized_glyph, fixup_factor);
         }
@@ -7,6 +7,7 @@
 #![deny(clippy::all, clippy::if_not_else, clippy::enum_glob_use)]
 
 use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(not(any(target_os = "macos", windows)))]
@@ -30,12 +31,57 @@ pub use darwin::CoreTextRasterizer as Rasterizer;
 const MAX_FONT_PT_SIZE: f32 = 3999.;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FontDesc {
     name: String,
     style: Style,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    variations: Vec<FontVariation>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    face_index: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    stretch: Option<u32>,
+}
+
+/// Coordinate on a variable font's design axis.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontVariation {
+    /// OpenType axis tag, like `wght` or `opsz`.
+    pub tag: [u8; 4],
+    pub value: f32,
+}
+
+impl FontVariation {
+    pub fn new(tag: [u8; 4], value: f32) -> Self {
+        Self { tag, value }
+    }
+}
+
+impl PartialEq for FontVariation {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag && self.value.to_bits() == other.value.to_bits()
+    }
+}
+
+impl Eq for FontVariation {}
+
+impl Hash for FontVariation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tag.hash(state);
+        self.value.to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for FontVariation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", String::from_utf8_lossy(&self.tag), self.value)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Slant {
     Normal,
     Italic,
@@ -43,13 +89,20 @@ pub enum Slant {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Weight {
     Normal,
     Bold,
 }
 
 /// Style of font.
+///
+/// With the `serde` feature, specific styles are represented by their name and descriptions
+/// by a map with `slant` and `weight`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
 pub enum Style {
     Specific(String),
     Description { slant: Slant, weight: Weight },
@@ -71,13 +124,116 @@ impl FontDesc {
     where
         S: Into<String>,
     {
-        FontDesc { name: name.into(), style }
+        FontDesc {
+            name: name.into(),
+            style,
+            variations: Vec::new(),
+            face_index: None,
+            stretch: None,
+        }
+    }
+
+    /// Create a font description from its individual properties.
+    ///
+    /// All properties default to the regular face of a font with an empty name.
+    pub fn builder() -> FontDescBuilder {
+        FontDescBuilder::default()
+    }
+
+    /// Instantiate a variable font at the given axis coordinates.
+    ///
+    /// Variations are currently only applied by the DirectWrite backend.
+    pub fn with_variations(mut self, variations: Vec<FontVariation>) -> Self {
+        self.variations = variations;
+        self
+    }
+
+    /// Select the face with the given index inside a font collection file.
+    ///
+    /// The index selects a face inside the file of the font matching the requested style. This is
+    /// currently only supported by the DirectWrite backend.
+    pub fn with_face_index(mut self, face_index: u32) -> Self {
+        self.face_index = Some(face_index);
+        self
+    }
+
+    /// Select the face with the given stretch.
+    ///
+    /// The stretch ranges from `1` for ultra-condensed to `9` for ultra-expanded faces, with `5`
+    /// being normal. This is currently only supported by the DirectWrite backend.
+    pub fn with_stretch(mut self, stretch: u32) -> Self {
+        self.stretch = Some(stretch.clamp(1, 9));
+        self
+    }
+}
+
+/// Builder for a [`FontDesc`] with a described style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontDescBuilder {
+    name: String,
+    weight: Weight,
+    slant: Slant,
+    stretch: u32,
+}
+
+impl Default for FontDescBuilder {
+    fn default() -> Self {
+        Self { name: String::new(), weight: Weight::Normal, slant: Slant::Normal, stretch: 5 }
+    }
+}
+
+impl FontDescBuilder {
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn weight(mut self, weight: Weight) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn slant(mut self, slant: Slant) -> Self {
+        self.slant = slant;
+        self
+    }
+
+    /// Set the stretch, see [`FontDesc::with_stretch`].
+    pub fn stretch(mut self, stretch: u32) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
+    pub fn build(self) -> FontDesc {
+        let style = Style::Description { slant: self.slant, weight: self.weight };
+        let desc = FontDesc::new(self.name, style);
+
+        // Keep descriptions of normal faces identical to the ones created without a stretch.
+        if self.stretch == 5 {
+            desc
+        } else {
+            desc.with_stretch(self.stretch)
+        }
     }
 }
 
 impl fmt::Display for FontDesc {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} - {}", self.name, self.style)
+        write!(f, "{} - {}", self.name, self.style)?;
+
+        for variation in &self.variations {
+            write!(f, ", {variation}")?;
+        }
+
+        if let Some(face_index) = self.face_index {
+            write!(f, ", index={face_index}")?;
+        }
+
+        if let Some(stretch) = self.stretch {
+            write!(f, ", stretch={stretch}")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -106,9 +262,29 @@ pub struct GlyphKey {
 }
 
 /// Font size stored as base and fraction.
+///
+/// Sizes are stored in points with a fixed precision, so they can be compared, ordered and used
+/// as keys. Conversions between points and pixels assume a display with 96 DPI, making one point
+/// equal to `96 / 72` pixels. The size in pixels is used as the em size for rasterization.
+///
+/// With the `serde` feature, sizes are represented by their value in points.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Size(u32);
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Size {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(self.as_pt())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Size {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f32::deserialize(deserializer).map(Size::new)
+    }
+}
+
 impl Size {
     /// Create a new `Size` from a f32 size in points.
     ///
@@ -118,12 +294,28 @@ impl Size {
         Size((size * Self::factor()) as u32)
     }
 
-    /// Create a new `Size` from px.
+    /// Create a new `Size` from a f32 size in points.
+    ///
+    /// This is identical to [`Size::new`].
+    pub fn from_pt(size: f32) -> Self {
+        Size::new(size)
+    }
+
+    /// Create a new `Size` from a f32 size in pixels at 96 DPI.
     ///
-    /// The value will be clamped to the pt range of [`Size::new`].
+    /// The value will be clamped to the pt range of [`Size::new`]. Integer pixel sizes are
+    /// converted without any loss, so [`Size::as_px`] returns the exact same value.
     pub fn from_px(size: f32) -> Self {
-        let pt = size * 72. / 96.;
-        Size::new(pt)
+        let pt = (f64::from(size) * 72. / 96.).clamp(1., f64::from(MAX_FONT_PT_SIZE));
+        Size((pt * f64::from(Self::factor())).round() as u32)
+    }
+
+    /// Create a new `Size` from a f32 size in points for a display with the given DPI.
+    ///
+    /// Font sizes are otherwise assumed to be for displays with 96 DPI. Since the DPI scale is
+    /// part of the size, metrics and glyph bitmaps are both reported in device pixels.
+    pub fn from_points(size: f32, dpi: f32) -> Self {
+        Size::new(size * dpi / 96.)
     }
 
     /// Scale font size by the given amount.
@@ -131,9 +323,9 @@ impl Size {
         Self::new(self.as_pt() * scale)
     }
 
-    /// Get size in `px`.
+    /// Get the size in `px` at 96 DPI.
     pub fn as_px(self) -> f32 {
-        self.as_pt() * 96. / 72.
+        (f64::from(self.0) / f64::from(Size::factor()) * 96. / 72.) as f32
     }
 
     /// Get the size in `pt`.
@@ -156,16 +348,241 @@ pub struct RasterizedGlyph {
     pub top: i32,
     pub left: i32,
     pub advance: (i32, i32),
+
+    /// Horizontal distances in pixels from the origin to the glyph's ink and from the ink to the
+    /// end of the advance.
+    ///
+    /// Side bearings are negative when the ink overhangs the advance box. Backends which don't
+    /// report them, like Core Text, leave both at zero.
+    pub side_bearings: (f32, f32),
+
     pub buffer: BitmapBuffer,
 }
 
+impl RasterizedGlyph {
+    /// Pixel format of the glyph's buffer.
+    pub fn format(&self) -> PixelFormat {
+        self.buffer.format()
+    }
+
+    /// Number of bytes per row of the glyph's buffer.
+    ///
+    /// Rows are tightly packed, without any padding between them.
+    pub fn stride(&self) -> usize {
+        match self.buffer {
+            BitmapBuffer::Bitmap1 { stride_bits, .. } => stride_bits / 8,
+            _ => self.width.max(0) as usize * self.format().bytes_per_pixel(),
+        }
+    }
+
+    /// Number of bytes per row of the glyph's buffer, see [`RasterizedGlyph::stride`].
+    pub fn width_bytes(&self) -> usize {
+        self.stride()
+    }
+
+    /// Raw bytes of the glyph's buffer, row by row from the top.
+    ///
+    /// This borrows the buffer, so it can be uploaded to a texture without copying it. Rows are
+    /// [`RasterizedGlyph::stride`] bytes long.
+    pub fn pixels(&self) -> &[u8] {
+        self.buffer.pixels()
+    }
+
+    /// Distance from the glyph origin to the top edge of the bitmap in pixels.
+    ///
+    /// Glyphs are currently always rasterized with their origin on a pixel boundary, so this is
+    /// exactly [`RasterizedGlyph::top`]. Layout engines should prefer it for positioning, since it
+    /// will carry the fractional part of the origin once glyphs can be rasterized at subpixel
+    /// offsets.
+    pub fn top_f32(&self) -> f32 {
+        self.top as f32
+    }
+
+    /// Distance from the glyph origin to the left edge of the bitmap in pixels.
+    ///
+    /// Like [`RasterizedGlyph::top_f32`], this is exactly [`RasterizedGlyph::left`] for glyphs
+    /// rasterized at integer origins.
+    pub fn left_f32(&self) -> f32 {
+        self.left as f32
+    }
+
+    /// Add transparent padding around the glyph's bitmap.
+    ///
+    /// The bitmap grows by `padding` pixels on every side, with `top` and `left` adjusted so the
+    /// glyph stays in place. The advance is not affected and glyphs without a bitmap or with a
+    /// packed [`BitmapBuffer::Bitmap1`] are returned unchanged.
+    pub fn pad(mut self, padding: u32) -> RasterizedGlyph {
+        if padding == 0
+            || self.width <= 0
+            || self.height <= 0
+            || matches!(self.buffer, BitmapBuffer::Bitmap1 { .. })
+        {
+            return self;
+        }
+
+        let padding = padding as i32;
+        let bytes_per_pixel = self.format().bytes_per_pixel();
+        let width = self.width + 2 * padding;
+        let height = self.height + 2 * padding;
+
+        let source_stride = self.stride();
+        let stride = width as usize * bytes_per_pixel;
+        let mut padded = vec![0; stride * height as usize];
+        let source = self.buffer.pixels();
+
+        let offset = padding as usize * (stride + bytes_per_pixel);
+        for (row, source_row) in source.chunks_exact(source_stride).enumerate() {
+            let start = offset + row * stride;
+            padded[start..start + source_stride].copy_from_slice(source_row);
+        }
+
+        self.buffer = match self.buffer {
+            BitmapBuffer::Rgb(_) => BitmapBuffer::Rgb(padded),
+            BitmapBuffer::Rgba(_) => BitmapBuffer::Rgba(padded),
+            BitmapBuffer::Alpha(_) => BitmapBuffer::Alpha(padded),
+            BitmapBuffer::Bitmap1 { .. } => unreachable!(),
+        };
+        self.width = width;
+        self.height = height;
+        self.top += padding;
+        self.left -= padding;
+
+        self
+    }
+
+    /// Threshold the glyph's coverage into a packed [`BitmapBuffer::Bitmap1`].
+    ///
+    /// Pixels with a coverage of at least `threshold` are set, the coverage of subpixel glyphs is
+    /// their brightest channel. Color glyphs and packed glyphs are returned unchanged.
+    pub fn to_mono(mut self, threshold: u8) -> RasterizedGlyph {
+        let channels = match self.buffer {
+            BitmapBuffer::Rgb(_) => 3,
+            BitmapBuffer::Alpha(_) => 1,
+            BitmapBuffer::Rgba(_) | BitmapBuffer::Bitmap1 { .. } => return self,
+        };
+
+        let width = self.width.max(0) as usize;
+        let stride_bits = width.div_ceil(8) * 8;
+        let mut data = vec![0; stride_bits / 8 * self.height.max(0) as usize];
+
+        if width > 0 {
+            let rows = self.buffer.pixels().chunks_exact(width * channels);
+            for (row, pixels) in rows.enumerate() {
+                for (column, pixel) in pixels.chunks_exact(channels).enumerate() {
+                    if pixel.iter().any(|&coverage| coverage >= threshold) {
+                        data[(row * stride_bits + column) / 8] |= 0x80 >> (column % 8);
+                    }
+                }
+            }
+        }
+
+        self.buffer = BitmapBuffer::Bitmap1 { data, stride_bits };
+        self
+    }
+
+    /// Flatten subpixel coverage into grayscale.
+    ///
+    /// The three subpixel channels of an [`BitmapBuffer::Rgb`] buffer are averaged in linear
+    /// space and stored in all channels, which allows compositing glyphs rasterized with
+    /// [`RenderingMode::Subpixel`] over transparent backgrounds. Other buffers are returned
+    /// unchanged.
+    pub fn to_grayscale(mut self) -> RasterizedGlyph {
+        if let BitmapBuffer::Rgb(buffer) = &mut self.buffer {
+            for pixel in buffer.chunks_exact_mut(3) {
+                let linear: f32 = pixel.iter().map(|&value| srgb_to_linear(value)).sum();
+                let coverage = linear_to_srgb(linear / 3.);
+                pixel.fill(coverage);
+            }
+        }
+
+        self
+    }
+}
+
+/// Gamma used for averaging subpixel coverage.
+const GAMMA: f32 = 2.2;
+
+fn srgb_to_linear(value: u8) -> f32 {
+    (f32::from(value) / 255.).powf(GAMMA)
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    (value.powf(1. / GAMMA) * 255.).round() as u8
+}
+
 #[derive(Clone, Debug)]
 pub enum BitmapBuffer {
     /// RGB alphamask.
     Rgb(Vec<u8>),
 
-    /// RGBA pixels with premultiplied alpha.
+    /// RGBA pixels, with premultiplied alpha unless the rasterizer is configured otherwise.
     Rgba(Vec<u8>),
+
+    /// Single channel alpha mask.
+    Alpha(Vec<u8>),
+
+    /// Packed monochrome bitmap with one bit per pixel.
+    ///
+    /// Pixels start at the most significant bit of each byte. Every row starts at a new byte, so
+    /// `stride_bits` is the width rounded up to a multiple of 8.
+    Bitmap1 { data: Vec<u8>, stride_bits: usize },
+}
+
+impl BitmapBuffer {
+    /// Pixel format of the buffer.
+    pub fn format(&self) -> PixelFormat {
+        match self {
+            BitmapBuffer::Rgb(_) => PixelFormat::Rgb8,
+            BitmapBuffer::Rgba(_) => PixelFormat::Rgba8,
+            BitmapBuffer::Alpha(_) => PixelFormat::A8,
+            BitmapBuffer::Bitmap1 { .. } => PixelFormat::Mono1,
+        }
+    }
+
+    /// Raw bytes of the buffer, without copying them.
+    pub fn pixels(&self) -> &[u8] {
+        match self {
+            BitmapBuffer::Rgb(buffer)
+            | BitmapBuffer::Rgba(buffer)
+            | BitmapBuffer::Alpha(buffer)
+            | BitmapBuffer::Bitmap1 { data: buffer, .. } => buffer,
+        }
+    }
+}
+
+/// Pixel format of a [`BitmapBuffer`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// Single channel alpha mask.
+    A8,
+
+    /// RGB alphamask.
+    Rgb8,
+
+    /// RGBA pixels, with premultiplied alpha unless the rasterizer is configured otherwise.
+    Rgba8,
+
+    /// Packed monochrome bitmap with one bit per pixel.
+    Mono1,
+}
+
+impl PixelFormat {
+    /// Number of bytes used by a single pixel.
+    ///
+    /// Packed formats are rounded up to a whole byte, see [`PixelFormat::bits_per_pixel`].
+    pub fn bytes_per_pixel(self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    /// Number of bits used by a single pixel.
+    pub fn bits_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Mono1 => 1,
+            PixelFormat::A8 => 8,
+            PixelFormat::Rgb8 => 24,
+            PixelFormat::Rgba8 => 32,
+        }
+    }
 }
 
 impl Default for RasterizedGlyph {
@@ -177,20 +594,116 @@ impl Default for RasterizedGlyph {
             top: 0,
             left: 0,
             advance: (0, 0),
+            side_bearings: (0., 0.),
             buffer: BitmapBuffer::Rgb(Vec::new()),
         }
     }
 }
 
+/// Drawing command of a glyph outline.
+///
+/// Coordinates are in pixels relative to the glyph origin on the baseline, with the Y axis
+/// pointing down like in screen space. Points above the baseline thus have negative Y values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathCommand {
+    /// Start a new contour.
+    MoveTo { x: f32, y: f32 },
+
+    /// Straight line to a point.
+    LineTo { x: f32, y: f32 },
+
+    /// Quadratic Bézier curve to a point.
+    QuadTo { cx: f32, cy: f32, x: f32, y: f32 },
+
+    /// Cubic Bézier curve to a point.
+    CubicTo { c0x: f32, c0y: f32, c1x: f32, c1y: f32, x: f32, y: f32 },
+
+    /// Close the current contour.
+    Close,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Metrics {
     pub average_advance: f64,
     pub line_height: f64,
+
+    /// Distance from the baseline to the top of the font's ascenders.
+    pub ascent: f32,
     pub descent: f32,
+
+    /// Additional spacing between lines recommended by the font.
+    pub line_gap: f32,
+
     pub underline_position: f32,
     pub underline_thickness: f32,
     pub strikeout_position: f32,
     pub strikeout_thickness: f32,
+
+    /// Position of an overline relative to the baseline.
+    ///
+    /// Fonts don't provide overline metrics, so the overline is placed right below the ascent,
+    /// with the thickness of the underline.
+    pub overline_position: f32,
+    pub overline_thickness: f32,
+
+    /// Position of the second, lower line of a double underline relative to the baseline.
+    pub double_underline_position: f32,
+}
+
+impl Metrics {
+    /// Get the position and thickness of the overline, see [`Self::overline_position`].
+    fn overline(ascent: f32, underline_thickness: f32) -> (f32, f32) {
+        (ascent - underline_thickness / 2., underline_thickness)
+    }
+}
+
+/// Pixel rectangle of a text decoration line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DecorationRect {
+    /// Distance from the baseline to the top of the line, positive values are below the
+    /// baseline.
+    pub y: i32,
+
+    /// Width of the line, which spans the whole cell.
+    pub width: i32,
+
+    /// Thickness of the line, which is at least one pixel.
+    pub height: i32,
+}
+
+impl DecorationRect {
+    /// Create the rectangle of a line centered on `position` above the baseline.
+    fn new(position: f32, thickness: f32, width: i32) -> Self {
+        let height = thickness.round().max(1.);
+        let y = (-position - height / 2.).round() as i32;
+        Self { y, width, height: height as i32 }
+    }
+}
+
+/// Pixel rectangles of all text decoration lines of a font.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DecorationRects {
+    pub underline: DecorationRect,
+
+    /// Second, lower line of a double underline.
+    pub double_underline: DecorationRect,
+
+    pub strikeout: DecorationRect,
+    pub overline: DecorationRect,
+}
+
+impl From<&Metrics> for DecorationRects {
+    fn from(metrics: &Metrics) -> Self {
+        let width = metrics.average_advance.round() as i32;
+        let rect = |position, thickness| DecorationRect::new(position, thickness, width);
+
+        Self {
+            underline: rect(metrics.underline_position, metrics.underline_thickness),
+            double_underline: rect(metrics.double_underline_position, metrics.underline_thickness),
+            strikeout: rect(metrics.strikeout_position, metrics.strikeout_thickness),
+            overline: rect(metrics.overline_position, metrics.overline_thickness),
+        }
+    }
 }
 
 /// Errors occuring when using the rasterizer.
@@ -199,6 +712,9 @@ pub enum Error {
     /// Unable to find a font matching the description.
     FontNotFound(FontDesc),
 
+    /// Unable to find a font or family with the given name.
+    FontNameNotFound(String),
+
     /// Unable to find metrics for a font face.
     MetricsNotFound,
 
@@ -208,6 +724,15 @@ pub enum Error {
     /// Requested an operation with a FontKey that isn't known to the rasterizer.
     UnknownFontKey,
 
+    /// The font has no variation axis with the requested tag.
+    UnknownVariationAxis([u8; 4]),
+
+    /// The backend or platform doesn't support a required feature.
+    Unsupported(&'static str),
+
+    /// The font size isn't a positive size in pixels.
+    InvalidSize(Size),
+
     /// Error from platfrom's font system.
     PlatformError(String),
 }
@@ -222,11 +747,17 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Error::FontNotFound(font) => write!(f, "font {font:?} not found"),
+            Error::FontNameNotFound(name) => write!(f, "font named {name:?} not found"),
             Error::MissingGlyph(glyph) => {
                 write!(f, "glyph for character {:?} not found", glyph.character)
             },
             Error::UnknownFontKey => f.write_str("invalid font key"),
+            Error::UnknownVariationAxis(tag) => {
+                write!(f, "font has no variation axis {:?}", String::from_utf8_lossy(tag))
+            },
             Error::MetricsNotFound => f.write_str("metrics not found"),
+            Error::Unsupported(feature) => write!(f, "{feature} is not supported"),
+            Error::InvalidSize(size) => write!(f, "invalid font size of {}px", size.as_px()),
             Error::PlatformError(err) => write!(f, "{err}"),
         }
     }
@@ -234,6 +765,8 @@ impl Display for Error {
 
 /// Font rendering mode for anti-aliasing.
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum RenderingMode {
     /// No anti-aliasing, grid-fitted pixel rendering.
     Aliased,
@@ -242,6 +775,11 @@ pub enum RenderingMode {
     Grayscale,
     /// Subpixel (ClearType) rendering.
     Subpixel,
+    /// Subpixel rendering for opaque backgrounds and grayscale anti-aliasing otherwise.
+    ///
+    /// Subpixel rendering is only used after the background was reported as opaque with
+    /// [`Rasterize::set_background_is_opaque`], so it is never used when the opacity is unknown.
+    Auto,
 }
 
 pub trait Rasterize {
@@ -253,9 +791,38 @@ pub trait Rasterize {
     /// Get `Metrics` for the given `FontKey`.
     fn metrics(&self, _: FontKey, _: Size) -> Result<Metrics, Error>;
 
+    /// Get the pixel rectangles of the decoration lines for the given `FontKey`.
+    ///
+    /// All lines are centered on their position in the `Metrics`, with their thickness rounded
+    /// to whole pixels.
+    fn decoration_rects(&self, key: FontKey, size: Size) -> Result<DecorationRects, Error> {
+        self.metrics(key, size).map(|metrics| DecorationRects::from(&metrics))
+    }
+
     /// Load the font described by `FontDesc` and `Size`.
     fn load_font(&mut self, _: &FontDesc, _: Size) -> Result<FontKey, Error>;
 
+    /// Load the first available font family from a list of names, like CSS `font-family`.
+    ///
+    /// If none of the families can be found, the returned [`Error::FontNotFound`] lists all
+    /// names separated by commas. Backends which always resolve a family to the closest match
+    /// will load the first name.
+    fn load_first_available(
+        &mut self,
+        names: &[String],
+        style: Style,
+        size: Size,
+    ) -> Result<FontKey, Error> {
+        for name in names {
+            match self.load_font(&FontDesc::new(name.as_str(), style.clone()), size) {
+                Err(Error::FontNotFound(_)) => continue,
+                result => return result,
+            }
+        }
+
+        Err(Error::FontNotFound(FontDesc::new(names.join(", "), style)))
+    }
+
     /// Rasterize the glyph described by `GlyphKey`..
     fn get_glyph(&mut self, _: GlyphKey) -> Result<RasterizedGlyph, Error>;
 
@@ -267,4 +834,11 @@ pub trait Rasterize {
 
     /// Enable or disable grid fitting (hinting).
     fn set_grid_fitting(&mut self, _enabled: bool) {}
+
+    /// Report whether glyphs are drawn over an opaque background.
+    ///
+    /// Subpixel coverage can't be composited over translucent backgrounds, so glyphs are
+    /// rendered with grayscale anti-aliasing instead of subpixel rendering when the background
+    /// is not opaque.
+    fn set_background_is_opaque(&mut self, _opaque: bool) {}
 }